@@ -0,0 +1,40 @@
+// Full-screen CRT effect: barrel curvature, scanlines and a vignette.
+// Samples the image the main camera rendered the game into.
+
+@group(1) @binding(0)
+var<uniform> intensity: f32;
+@group(1) @binding(1)
+var source_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var source_sampler: sampler;
+
+// One dark line every other pixel row at 720p
+const SCANLINES: f32 = 360.0;
+const CURVATURE: f32 = 0.08;
+const PI: f32 = 3.14159265;
+
+// Push the uv outwards from the centre, like the glass of an old CRT
+fn curve(uv: vec2<f32>, amount: f32) -> vec2<f32> {
+	let centred = uv * 2.0 - 1.0;
+	let offset = centred.yx * centred.yx * amount;
+	return (centred + centred * offset) * 0.5 + 0.5;
+}
+
+@fragment
+fn fragment(
+	#import bevy_sprite::mesh2d_vertex_output
+) -> @location(0) vec4<f32> {
+	let curved = curve(uv, CURVATURE * intensity);
+	var colour = textureSample(source_texture, source_sampler, curved).rgb;
+
+	let scanline = 0.5 + 0.5 * cos(curved.y * SCANLINES * 2.0 * PI);
+	colour = colour * mix(1.0, 0.7 + 0.3 * scanline, intensity);
+
+	let edge = curved * (1.0 - curved);
+	let vignette = clamp(pow(edge.x * edge.y * 16.0, 0.25), 0.0, 1.0);
+	colour = colour * mix(1.0, vignette, intensity);
+
+	// Anything curved off the edge of the screen is just black bezel
+	let inside = all(curved >= vec2<f32>(0.0)) && all(curved <= vec2<f32>(1.0));
+	return select(vec4<f32>(0.0, 0.0, 0.0, 1.0), vec4<f32>(colour, 1.0), inside);
+}
@@ -0,0 +1,7688 @@
+use bevy::{
+	prelude::*,
+
+	core_pipeline::bloom::BloomSettings,
+
+	sprite::{collide_aabb::collide, Material2d, Material2dPlugin, MaterialMesh2dBundle, Mesh2dHandle},
+
+	reflect::TypeUuid,
+	render::{
+		camera::RenderTarget,
+		render_resource::{
+			AsBindGroup, Extent3d, ShaderRef, TextureDescriptor, TextureDimension, TextureFormat,
+			TextureUsages,
+		},
+		settings::WgpuSettings,
+		view::RenderLayers,
+		RenderPlugin,
+	},
+	transform::TransformSystem,
+	asset::LoadState,
+
+	app::AppExit,
+	window::{
+		ExitCondition, MonitorSelection, PresentMode, PrimaryWindow, WindowCloseRequested, WindowMoved,
+		WindowPosition, WindowResolution,
+	},
+	winit::{WinitPlugin, WinitWindows},
+	time::TimeUpdateStrategy,
+	input::common_conditions::input_toggle_active,
+	diagnostic::{Diagnostics, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin},
+};
+
+use bevy_inspector_egui::quick::WorldInspectorPlugin;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+
+#[cfg(feature = "devtools")]
+use bevy::ecs::system::SystemState;
+
+const WIDTH: f32 = 1280.0;
+const HEIGHT: f32 = 720.0;
+const TIME_STEP: f32 = 1.0 / 60.0;
+// Gameplay positions are snapped to this fraction of a pixel at the end of every
+// tick, so rounding differences between platforms can't build up over a run
+const POSITION_GRID: f32 = 256.0;
+
+const BUTTON_FONT_SIZE: f32 = 20.0;
+
+// Challenge codes pack a run's seed and options into a few bytes, written out
+// in base32 so they're easy to read out and type in. The version goes up
+// whenever the layout or anything the seed drives changes, so old codes are
+// turned away rather than playing out differently.
+const CHALLENGE_VERSION: u8 = 2;
+const CHALLENGE_CODE_LEN: usize = 13;
+// Before difficulty was added. Still recognised, to say why they don't work.
+const CHALLENGE_V1_CODE_LEN: usize = 12;
+const CHALLENGE_ALPHABET: &[u8; 32] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ234567";
+const CHALLENGE_INPUT_FONT_SIZE: f32 = 40.0;
+const CHALLENGE_ERROR_COLOUR: Color = Color::rgb(1.0, 0.4, 0.4);
+
+// Dims the paused game behind the quit confirmation
+const QUIT_DIALOG_BG_COLOUR: Color = Color::rgba(0.0, 0.0, 0.0, 0.7);
+
+// The CRT quad and its camera live on their own render layer so the main
+// camera never draws them
+const CRT_RENDER_LAYER: u8 = 1;
+// How much a 0-1 setting (CRT, bloom strength) changes with each click in the
+// settings menu
+const SETTINGS_FRACTION_STEP: f32 = 0.1;
+
+// How far past 1.0 bullet colours are pushed when bloom is on, so they glow
+const BULLET_GLOW: f32 = 4.0;
+
+// Where the player's settings are saved, relative to the working directory
+const SETTINGS_PATH: &str = "settings.ron";
+// Dragging a window sends a move event every frame, so its position is only
+// saved once it's been still for this long
+const WINDOW_SAVE_DELAY_SECS: f32 = 0.5;
+const WINDOW_TITLE: &str = "Aidan's Competency Project - Bevy Invaders!";
+// During a run the title shows the wave and score, but only changes this often
+const WINDOW_TITLE_REFRESH_SECS: f32 = 1.0;
+const WINDOW_TITLE_MAX_CHARS: usize = 100;
+const ENEMY_CATALOG_PATH: &str = "assets/enemies.ron";
+const FORMATIONS_PATH: &str = "assets/formations.ron";
+// One RON file of UI strings per language, named after its code
+const LANGUAGE_DIR: &str = "assets/lang";
+// Each sound is looked for with these extensions in order, so an .ogg can be
+// dropped in next to a .wav to replace it
+const AUDIO_FORMATS: [&str; 2] = ["ogg", "wav"];
+const UI_FONT: &str = "fonts/amiga4ever/amiga4ever.ttf";
+
+const CLASSIC_THEME: Theme = Theme {
+	background: Color::BLACK,
+	button: Color::rgb(0.15, 0.15, 0.15),
+	button_hovered: Color::rgb(0.25, 0.25, 0.25),
+	button_text: Color::rgb(0.9, 0.9, 0.9),
+	bullet: Color::WHITE,
+	scoreboard: Color::AZURE,
+	enemy: Color::LIME_GREEN,
+};
+
+const HIGH_CONTRAST_THEME: Theme = Theme {
+	background: Color::BLACK,
+	button: Color::rgb(0.0, 0.0, 0.55),
+	button_hovered: Color::rgb(0.0, 0.0, 0.9),
+	button_text: Color::WHITE,
+	bullet: Color::YELLOW,
+	scoreboard: Color::WHITE,
+	enemy: Color::CYAN,
+};
+
+// Avoids relying on red/green, using the blue/orange pair instead
+const DEUTERANOPIA_THEME: Theme = Theme {
+	background: Color::BLACK,
+	button: Color::rgb(0.15, 0.15, 0.15),
+	button_hovered: Color::rgb(0.25, 0.25, 0.25),
+	button_text: Color::rgb(0.9, 0.9, 0.9),
+	bullet: Color::rgb(0.9, 0.6, 0.0),
+	scoreboard: Color::rgb(0.95, 0.9, 0.25),
+	enemy: Color::rgb(0.0, 0.45, 0.7),
+};
+
+const BULLET_SPEED: f32 = 400.0;
+const BULLET_SIZE: Vec2 = Vec2::new(10.0, 25.0);
+// With the Ricochet mutator, how many times a bullet bounces off the side walls
+// and how much faster it comes away each time
+const RICOCHET_BOUNCES: u32 = 1;
+const RICOCHET_SPEED_BOOST: f32 = 1.15;
+
+// Player bullets leave a line of fading after-images, one every TRAIL_TICKS ticks
+const TRAIL_TICKS: u32 = 2;
+const TRAIL_SECS: f32 = 0.15;
+const TRAIL_ALPHA: f32 = 0.5;
+// Stops a screen full of bullets turning into thousands of trail sprites
+const TRAIL_MAX_SEGMENTS: usize = 200;
+
+// Dead enemies leave a few bits of debris that tumble away and fade. Purely for
+// looks, they never collide with anything.
+const DEBRIS_SIZE: f32 = 6.0;
+const DEBRIS_SECS: f32 = 1.5;
+const DEBRIS_FALL_SPEED: f32 = 100.0;
+const DEBRIS_SPREAD_SPEED: f32 = 60.0;
+// Radians per second
+const DEBRIS_SPIN: f32 = 4.0;
+// Oldest debris is cleared first past this, so a screen of kills can't flood it
+const DEBRIS_MAX: usize = 60;
+
+// Scrap dropped by dead enemies. It falls, blinks for its last few seconds and
+// is gone if the player doesn't fly into it first.
+const PICKUP_FALL_SPEED: f32 = 80.0;
+const PICKUP_SECS: f32 = 6.0;
+const PICKUP_BLINK_SECS: f32 = 2.0;
+const PICKUP_BLINKS_PER_SEC: f32 = 8.0;
+const PICKUP_COLOUR: Color = Color::GOLD;
+// How fast pickups inside the magnet radius fly to the player
+const PICKUP_MAGNET_SPEED: f32 = 300.0;
+
+// Firing kicks the ship back a little and flashes at the nose, both purely visual
+const RECOIL_DISTANCE: f32 = 3.5;
+const RECOIL_SECS: f32 = 2.0 * TIME_STEP;
+const MUZZLE_FLASH_SIZE: Vec2 = Vec2::new(14.0, 8.0);
+const MUZZLE_FLASH_SECS: f32 = 0.05;
+
+const SCOREBOARD_FONT_SIZE: f32 = 24.0;
+// The thin bar under the HUD showing how much of the wave is dead
+const WAVE_PROGRESS_WIDTH: f32 = 300.0;
+const WAVE_PROGRESS_HEIGHT: f32 = 4.0;
+const WAVE_PROGRESS_TOP: f32 = 5.0 + SCOREBOARD_FONT_SIZE + 3.0;
+// How quickly the bar catches up with a kill, higher is snappier
+const WAVE_PROGRESS_SMOOTHING: f32 = 12.0;
+const WAVE_PROGRESS_TRACK_ALPHA: f32 = 0.2;
+// However big the jump, the displayed score catches up with the real one in this long
+const SCORE_CATCHUP_SECS: f32 = 0.5;
+const SCORE_CLIMBING_COLOUR: Color = Color::WHITE;
+
+const PLAYER_SPEED: f32 = 500.0;
+// The player sprite is 32x32, drawn at GameConfig::player_scale. The real size
+// of the fallback image is read once it loads, this is only used until then.
+const PLAYER_SPRITE_SIZE: f32 = 32.0;
+
+// Downwards acceleration on player bullets with the Bullet Gravity mutator
+const BULLET_GRAVITY: f32 = 300.0;
+// The dotted line showing where a shot will go with Bullet Gravity on. One dot
+// every ARC_PREVIEW_TICKS_PER_DOT ticks of flight.
+const ARC_PREVIEW_DOTS: usize = 24;
+const ARC_PREVIEW_TICKS_PER_DOT: usize = 4;
+const ARC_PREVIEW_DOT_SIZE: f32 = 3.0;
+const ARC_PREVIEW_ALPHA: f32 = 0.3;
+// Score bonus for playing with the Tiny Player mutator
+const TINY_PLAYER_SCORE_MULTIPLIER: f32 = 1.25;
+// With Wrap the formation comes down less often, but it's harder to line shots
+// up on. It drops once for every lap this far.
+const WRAP_SCORE_MULTIPLIER: f32 = 1.1;
+const WRAP_LAP_DISTANCE: f32 = WIDTH + ENEMY_SIZE.x;
+const MUTATORS_HUD_FONT_SIZE: f32 = 14.0;
+
+const EVENT_LOG_FONT_SIZE: f32 = 14.0;
+const EVENT_LOG_MAX_LINES: usize = 4;
+// How long a line stays in the event log, the last part of which it spends fading out
+const EVENT_LOG_LINE_SECS: f32 = 3.0;
+const EVENT_LOG_FADE_SECS: f32 = 1.0;
+
+// The size of a formation slot. Each kind's own size is in the enemy catalog.
+const ENEMY_SIZE: Vec2 = Vec2::new(40.0, 30.0);
+const ENEMY_SPACING: Vec2 = Vec2::new(60.0, 50.0);
+const ENEMY_ROWS: usize = 5;
+const ENEMY_COLUMNS: usize = 11;
+// The y of the top row of the formation when it spawns
+const ENEMY_GRID_TOP: f32 = (HEIGHT / 2.0) - 100.0;
+// The formation marching behind the main menu: fainter, slower, and starting
+// again from the top once it gets this far down
+const MENU_DEMO_ALPHA: f32 = 0.2;
+const MENU_DEMO_SPEED: f32 = 0.5;
+const MENU_DEMO_LOWEST: f32 = -(HEIGHT / 2.0) + 100.0;
+// Gap kept between the outermost live enemy and the edge of the screen
+const FORMATION_EDGE_MARGIN: f32 = 10.0;
+// Purely for looks, the formation bobs up and down and leans into the march
+const FORMATION_BOB_HEIGHT: f32 = 3.0;
+const FORMATION_BOB_SPEED: f32 = 3.0;
+const FORMATION_BANK_DEGREES: f32 = 5.0;
+// How quickly the lean swings over when the formation turns around (per second)
+const FORMATION_BANK_SMOOTHING: f32 = 6.0;
+// Arrows flash at the wall the formation is heading for this long before it drops
+const DESCENT_WARNING_SECS: f32 = 0.5;
+const DESCENT_WARNING_FLASH_SECS: f32 = 0.08;
+const DESCENT_TICK_VOLUME: f32 = 0.3;
+const DESCENT_ARROW_SIZE: Vec2 = Vec2::new(2.0, 12.0);
+const DESCENT_ARROWS_PER_SIDE: usize = 3;
+
+// From the second wave on, the formation flies in from off screen rather than appearing
+const FLY_IN_SECS: f32 = 2.0;
+
+// End of wave bonuses for how many of the wave's shots hit
+const WAVE_PERFECT_ACCURACY_BONUS: usize = 1000;
+const WAVE_ACCURACY_BONUS: usize = 250;
+const WAVE_ACCURACY_BONUS_THRESHOLD: f32 = 0.8;
+// The tally shows one bonus line this often, then holds for the same again
+const WAVE_TALLY_LINE_SECS: f32 = 0.5;
+const WAVE_TALLY_FONT_SIZE: f32 = 20.0;
+
+const WAVE_BANNER_SECS: f32 = 1.5;
+// How long the banner spends sliding in, and again fading out
+const WAVE_BANNER_FADE_SECS: f32 = 0.3;
+const WAVE_BANNER_FONT_SIZE: f32 = 40.0;
+const WAVE_BANNER_SUBTITLE_FONT_SIZE: f32 = 16.0;
+// Waves worth announcing as more than just a number, as string keys
+const WAVE_SUBTITLES: &[(u32, &str)] = &[
+	(5, "wave.getting_faster"),
+	(10, "wave.no_slowing_down"),
+];
+
+// The same sound started again within this long is dropped, it'd only sound louder
+const SOUND_DEDUP_SECS: f32 = 0.03;
+// How far shots and enemy deaths wander from their recorded pitch, 0.1 is +-10%
+const SHOOT_PITCH_VARIANCE: f32 = 0.05;
+const ENEMY_DEATH_PITCH_VARIANCE: f32 = 0.1;
+
+const MUSIC_VOLUME: f32 = 0.5;
+// How quickly the tension layer's volume catches up with the tension (per second)
+const MUSIC_TENSION_SMOOTHING: f32 = 2.0;
+
+// How long gameplay freezes for on each kill, and the most it can build up to
+// when lots of enemies die at once
+const HITSTOP_TICKS: u32 = 3;
+// Killing the last enemy of a wave slows everything down and zooms in on it for a moment
+const FINISHER_SPEED: f32 = 0.25;
+const FINISHER_SECS: f32 = 0.6;
+const FINISHER_ZOOM: f32 = 0.7;
+const HITSTOP_MAX_TICKS: u32 = 6;
+
+const DEBUG_OVERLAY_FONT_SIZE: f32 = 14.0;
+const DEBUG_OVERLAY_COLOUR: Color = Color::YELLOW;
+// How often the overlay text is refreshed, so the numbers are actually readable
+const DEBUG_OVERLAY_REFRESH_SECS: f32 = 0.25;
+
+#[cfg(feature = "devtools")]
+const CONSOLE_FONT_SIZE: f32 = 14.0;
+#[cfg(feature = "devtools")]
+const CONSOLE_BG_COLOUR: Color = Color::rgba(0.0, 0.0, 0.0, 0.8);
+#[cfg(feature = "devtools")]
+const CONSOLE_SCROLLBACK_COLOUR: Color = Color::GRAY;
+#[cfg(feature = "devtools")]
+const CONSOLE_INPUT_COLOUR: Color = Color::WHITE;
+#[cfg(feature = "devtools")]
+const CONSOLE_SCROLLBACK_LINES: usize = 10;
+#[cfg(feature = "devtools")]
+const GOD_LABEL_COLOUR: Color = Color::GOLD;
+// The longest the console's slow command will slow the formation for
+#[cfg(feature = "devtools")]
+const CONSOLE_SLOW_MAX_SECS: f32 = 600.0;
+// The chaos test despawns one gameplay entity every interval, for this long
+#[cfg(feature = "devtools")]
+const CHAOS_TEST_SECS: f32 = 60.0;
+// The --bench stress scene: this many waves of enemies stacked up, this many
+// bullets, timed over this many fixed ticks
+#[cfg(feature = "devtools")]
+const BENCH_WAVES: usize = 8;
+#[cfg(feature = "devtools")]
+const BENCH_BULLETS: usize = 500;
+#[cfg(feature = "devtools")]
+const BENCH_TICKS: usize = 1000;
+#[cfg(feature = "devtools")]
+const CHAOS_TEST_INTERVAL_SECS: f32 = 1.0;
+// Asset collections are counted this often. After the warm-up, one that grows
+// at every sample for a minute straight is probably leaking.
+#[cfg(feature = "devtools")]
+const ASSET_WATCH_INTERVAL_SECS: f32 = 10.0;
+#[cfg(feature = "devtools")]
+const ASSET_WATCH_WARMUP_SAMPLES: usize = 3;
+#[cfg(feature = "devtools")]
+const ASSET_WATCH_GROWTH_SAMPLES: usize = 6;
+
+/// Which screen the game is on. Gameplay systems only run in `GameRunning`.
+#[derive(Debug, Default, Clone, Eq, PartialEq, Hash, States)]
+pub enum AppState {
+	/// The main menu, where the game starts
+	#[default]
+	Menu,
+	/// The settings screen
+	Settings,
+	/// Picking mutators before a run
+	Mutators,
+	/// Typing in a challenge code
+	Challenge,
+
+	/// A run is being played
+	GameRunning,
+	/// After a run, once the player is hit
+	GameOver,
+}
+
+// Gameplay systems run in these sets, in this order, both on the fixed tick and
+// in the per-frame Update. Anything ordered against a whole stage goes through
+// the set rather than naming systems in other stages.
+//
+// - Input: reading the player's controls
+// - Simulation: moving things and advancing timers that affect the game
+// - CollisionResolution: working out hits and reacting to them
+// - Cleanup: removing what's finished and starting the next wave
+// - Presentation: sounds, HUD and purely visual effects
+#[derive(SystemSet, Debug, Clone, PartialEq, Eq, Hash)]
+enum GameSet {
+	Input,
+	Simulation,
+	CollisionResolution,
+	Cleanup,
+	Presentation,
+}
+
+/// Builds the whole game, with every plugin, resource and system registered,
+/// ready to `run()`. The binary is just this.
+pub fn build_game_app() -> App {
+	let mut app = App::new();
+
+	app
+		.add_plugins(
+			DefaultPlugins
+			.set(WindowPlugin {
+				primary_window: Some(Window {
+					title: WINDOW_TITLE.to_string(),
+					resolution: WindowResolution::new(WIDTH, HEIGHT).with_scale_factor_override(1.0),
+					present_mode: PresentMode::AutoVsync,
+					fit_canvas_to_parent: true,
+					prevent_default_event_handling: true,
+					resizable: false,
+					..default()
+				}),
+				// handle_close_requests asks first if a run is going
+				close_when_requested: false,
+				..default()
+			}).set(ImagePlugin::default_nearest())
+		)
+		.add_plugin(WorldInspectorPlugin::default().run_if(input_toggle_active(false, KeyCode::F12)))
+		.add_plugin(GamePlugin);
+
+	app
+}
+
+/// The game without a window, GPU or sound card, for tests. Nothing is drawn,
+/// and every `update()` moves time on by exactly one fixed tick, so a run plays
+/// out the same however fast the machine is.
+pub fn build_headless_app() -> App {
+	let mut app = App::new();
+
+	app
+		.add_plugins(
+			DefaultPlugins
+			.set(WindowPlugin {
+				primary_window: None,
+				exit_condition: ExitCondition::DontExit,
+				close_when_requested: false,
+			})
+			// No backends means no render world at all
+			.set(RenderPlugin {
+				wgpu_settings: WgpuSettings {
+					backends: None,
+					..default()
+				},
+			})
+			.disable::<WinitPlugin>()
+		)
+		.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(TIME_STEP)))
+		.add_plugin(GamePlugin);
+
+	app
+}
+
+/// Everything that makes up the game itself: its resources, states and systems,
+/// plus [`DevToolsPlugin`] when the `devtools` feature is on. Needs bevy's
+/// `DefaultPlugins` added first, with or without a window.
+pub struct GamePlugin;
+
+impl Plugin for GamePlugin {
+	fn build(&self, app: &mut App) {
+		let settings = Settings::load();
+		let theme = settings.theme.theme();
+		let enemy_catalog = EnemyCatalog::load();
+		let formations = Formations::load();
+		let strings = Strings::load(settings.language);
+
+		app
+			.add_plugin(FrameTimeDiagnosticsPlugin::default())
+			.add_plugin(EntityCountDiagnosticsPlugin::default())
+			.insert_resource(ClearColor(theme.background))
+			.insert_resource(theme)
+			.insert_resource(settings)
+			.register_type::<Settings>()
+			.insert_resource(enemy_catalog)
+			.insert_resource(formations)
+			.insert_resource(strings)
+			.add_system(reload_strings.after(settings_menu))
+			.register_type::<EnemyCatalog>()
+			.init_resource::<Mutators>()
+			.register_type::<Mutators>()
+			.init_resource::<PlayfieldOrientation>()
+			.init_resource::<GameConfig>()
+			.register_type::<GameConfig>()
+			.init_resource::<AssistSettings>()
+			.register_type::<AssistSettings>()
+			.init_resource::<Wave>()
+			.init_resource::<FormationParams>()
+			.init_resource::<FormationModifiers>()
+			.register_type::<FormationParams>()
+			.add_state::<AppState>()
+			.add_startup_system(setup)
+			.add_system(menu_setup.in_schedule(OnEnter(AppState::Menu)))
+			.add_system(menu.in_set(OnUpdate(AppState::Menu)))
+			.add_system(menu_cleanup.in_schedule(OnExit(AppState::Menu)))
+			.add_systems(
+				(
+					march_formation::<MenuDemo>,
+					loop_menu_demo.after(march_formation::<MenuDemo>),
+				)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::Menu))
+					.distributive_run_if(|settings: Res<Settings>| !settings.reduced_motion)
+			)
+			.add_system(settings_menu_setup.in_schedule(OnEnter(AppState::Settings)))
+			.add_system(settings_menu.in_set(OnUpdate(AppState::Settings)))
+			.add_system(update_settings_labels.in_set(OnUpdate(AppState::Settings)).after(settings_menu))
+			.add_system(settings_menu_cleanup.in_schedule(OnExit(AppState::Settings)))
+			.add_system(mutators_menu_setup.in_schedule(OnEnter(AppState::Mutators)))
+			.add_system(mutators_menu.in_set(OnUpdate(AppState::Mutators)))
+			.add_system(update_mutator_labels.in_set(OnUpdate(AppState::Mutators)).after(mutators_menu))
+			.add_system(mutators_menu_cleanup.in_schedule(OnExit(AppState::Mutators)))
+			.init_resource::<ActiveChallenge>()
+			.init_resource::<ChallengeInput>()
+			.init_resource::<RunCode>()
+			.init_resource::<SavedDifficulty>()
+			.add_system(clear_challenge.in_schedule(OnEnter(AppState::Menu)))
+			.add_system(challenge_menu_setup.in_schedule(OnEnter(AppState::Challenge)))
+			.add_systems(
+				(
+					challenge_input,
+					challenge_menu,
+					update_challenge_text,
+				)
+					.chain()
+					.in_set(OnUpdate(AppState::Challenge))
+			)
+			.add_system(challenge_menu_cleanup.in_schedule(OnExit(AppState::Challenge)))
+			// reset_run's commands are applied before game_setup, so it never sees
+			// the last run's entities or scoreboard
+			.add_systems(
+				(
+					reset_run,
+					apply_system_buffers,
+					game_setup,
+				)
+					.chain()
+					.in_schedule(OnEnter(AppState::GameRunning))
+			)
+			.add_system(start_music.in_schedule(OnEnter(AppState::GameRunning)))
+			.add_system(stop_music.in_schedule(OnExit(AppState::GameRunning)))
+			.configure_sets(
+				(
+					GameSet::Input,
+					GameSet::Simulation,
+					GameSet::CollisionResolution,
+					GameSet::Cleanup,
+					GameSet::Presentation,
+				).chain()
+			)
+			.edit_schedule(CoreSchedule::FixedUpdate, |schedule| {
+				schedule.configure_sets(
+					(
+						GameSet::Input,
+						GameSet::Simulation,
+						GameSet::CollisionResolution,
+						GameSet::Cleanup,
+						GameSet::Presentation,
+					).chain()
+				);
+			})
+			// Anything that moves things or checks where they are runs on the fixed tick.
+			// New things get their SpawnId first, applied straight away so the whole
+			// tick can sort by it.
+			.add_systems(
+				(
+					assign_spawn_ids,
+					apply_system_buffers,
+				)
+					.chain()
+					.before(GameSet::Input)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					store_previous_transforms,
+					update_hitstop.after(store_previous_transforms),
+					move_player.after(store_previous_transforms),
+				)
+					.in_set(GameSet::Input)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					fly_in_enemies,
+					tick_formation_modifiers.run_if(not_in_hitstop),
+					march_formation::<Enemy>
+						.after(fly_in_enemies)
+						.after(tick_formation_modifiers)
+						.run_if(not_in_hitstop),
+					apply_bullet_gravity
+						.run_if(not_in_hitstop)
+						.run_if(|mutators: Res<Mutators>| mutators.bullet_gravity),
+					apply_velocity
+						.after(apply_bullet_gravity)
+						.run_if(not_in_hitstop),
+					ricochet_bullets
+						.after(apply_velocity)
+						.run_if(not_in_hitstop),
+					apply_angular_velocity.run_if(not_in_hitstop),
+					attract_pickups
+						.before(apply_velocity)
+						.run_if(|config: Res<GameConfig>| config.pickup_magnet_radius > 0.0),
+				)
+					.in_set(GameSet::Simulation)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					collision_check
+						.run_if(not_in_hitstop)
+						.run_if(resource_exists::<Scoreboard>()),
+					trigger_hitstop.after(collision_check),
+					trigger_finisher.after(collision_check),
+					end_run_on_hit.after(collision_check),
+				)
+					.in_set(GameSet::CollisionResolution)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					remove_offscreen_entities,
+					count_wave_kills.before(next_wave),
+					next_wave,
+					spawn_pickups,
+					quantise_positions,
+				)
+					.in_set(GameSet::Cleanup)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.distributive_run_if(in_state(AppState::GameRunning))
+			)
+			.add_system(
+				spawn_bullet_trails
+					.in_set(GameSet::Presentation)
+					.in_schedule(CoreSchedule::FixedUpdate)
+					.run_if(in_state(AppState::GameRunning))
+			)
+			// And the rest once a frame
+			.add_systems(
+				(
+					read_fire_input,
+					fire_weapon,
+				)
+					.chain()
+					.in_set(GameSet::Input)
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					fall_back_to_player_sprite,
+					update_player_bounds,
+					update_finisher,
+					update_wave_tally.run_if(resource_exists::<Scoreboard>()),
+					update_kill_streaks.run_if(resource_exists::<Scoreboard>()),
+					award_shot_points.run_if(resource_exists::<Scoreboard>()),
+					update_high_score.run_if(resource_exists_and_changed::<Scoreboard>()),
+				)
+					.in_set(GameSet::Simulation)
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			.add_system(
+				warn_missing_game_state
+					.in_set(GameSet::Cleanup)
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			.add_systems(
+				(
+					play_shot_sounds,
+					play_hit_sound,
+					update_music_tension,
+					update_scoreboard.run_if(resource_exists::<Scoreboard>()),
+					// The rest of the HUD only needs redrawing when what it shows changes
+					update_wave_text.run_if(resource_exists_and_changed::<Wave>()),
+					update_high_score_text.run_if(resource_changed::<HighScore>()),
+					update_event_log,
+					update_wave_banner,
+					update_currency_text.run_if(resource_exists_and_changed::<Currency>()),
+					update_pickups,
+					update_wave_progress,
+				)
+					.in_set(GameSet::Presentation)
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			// Visual effects
+			.add_systems(
+				(
+					flash_killed_enemies,
+					animate_formation,
+					update_bullet_trails,
+					shot_feedback.before(update_recoil),
+					update_recoil,
+					update_player_frame,
+					update_muzzle_flashes,
+					update_arc_preview,
+					update_descent_warning,
+					spawn_debris,
+					update_debris,
+				)
+					.in_set(GameSet::Presentation)
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			.add_system(
+				interpolate_transforms
+					.in_base_set(CoreSet::PostUpdate)
+					.after(TransformSystem::TransformPropagate)
+			)
+			.add_event::<CollisionEvent>()
+			.add_event::<PlayerHitEvent>()
+			.add_event::<PlaySound>()
+			.add_event::<FireIntent>()
+			.add_event::<ShotFired>()
+			.add_event::<EnemyKilledEvent>()
+			.add_event::<GameMessage>()
+
+			// Make the calculations run 60 times per second, making it separate from the framerate
+			// otherwise janky stuff can happen at high framerates (looking at you, Skyrim)
+			.insert_resource(FixedTime::new_from_secs(TIME_STEP))
+			.insert_resource(Scoreboard { score: 0 })
+			.init_resource::<DisplayedScore>()
+			.init_resource::<HighScore>()
+			.init_resource::<Currency>()
+			.init_resource::<GameRng>()
+			.init_resource::<NextSpawnId>()
+			.add_system(
+				snap_displayed_score
+					.run_if(resource_exists::<Scoreboard>())
+					.in_schedule(OnEnter(AppState::GameOver))
+			)
+			.init_resource::<TaintedRun>()
+			.init_resource::<GodMode>()
+			.init_resource::<Hitstop>()
+			.init_resource::<SoundManager>()
+			.init_resource::<GameSpeed>()
+			.init_resource::<Finisher>()
+			.add_system(apply_game_speed)
+			.init_resource::<Monitors>()
+			.init_resource::<PlayerBounds>()
+			.add_system(apply_window_settings)
+			.add_system(remember_window_position.before(save_settings))
+			.add_system(update_window_title)
+			// After quit_dialog, so the Esc that cancels the dialog doesn't open it again
+			.add_system(handle_close_requests.after(quit_dialog))
+			.add_system(game_over_input.in_set(OnUpdate(AppState::GameOver)))
+			.add_system(quit_dialog.before(GameSet::Input))
+			// Menus make sounds too, so this isn't tied to a state
+			.add_system(update_sound_manager.before(GameSet::Presentation))
+			.add_system(ui_feedback.before(audio_playback))
+			// After anything that asks for a sound, so it's heard the same frame
+			.add_system(audio_playback.after(update_sound_manager).after(GameSet::Presentation))
+			.add_system(stop_finisher.in_schedule(OnExit(AppState::GameRunning)))
+			.add_system(wave_banner_cleanup.in_schedule(OnExit(AppState::GameRunning)))
+			.init_resource::<RunStats>()
+			.init_resource::<WaveTally>()
+			.add_system(wave_tally_cleanup.in_schedule(OnExit(AppState::GameRunning)))
+			.add_system(save_settings)
+			.add_system(sync_theme)
+			.add_system(sync_assist)
+			.add_system(apply_theme.after(sync_theme))
+			.add_plugin(Material2dPlugin::<CrtMaterial>::default())
+			.add_startup_system(crt_setup.after(setup))
+			.add_system(apply_crt_settings)
+			.add_system(apply_bloom_settings.after(apply_theme))
+			.insert_resource(DebugOverlayTimer(Timer::from_seconds(DEBUG_OVERLAY_REFRESH_SECS, TimerMode::Repeating)))
+			.add_system(toggle_debug_overlay)
+			.add_system(update_debug_overlay.after(toggle_debug_overlay));
+
+		#[cfg(feature = "devtools")]
+		app.add_plugin(DevToolsPlugin);
+	}
+}
+
+/// The player's ship
+#[derive(Component)]
+pub struct Player;
+
+// Which way the player is moving, -1 to 1, for picking the banking frame
+#[derive(Component, Default)]
+struct PlayerFacing(f32);
+
+// Anything that belongs to a single run, cleared away before the next one starts
+#[derive(Component)]
+struct GameEntity;
+
+// Handed out in a fixed order as gameplay things appear. Anything where the
+// order matters sorts by this, since Entity ids depend on whatever the UI
+// happened to spawn that frame.
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct SpawnId(u64);
+
+#[derive(Resource, Default)]
+struct NextSpawnId(u64);
+
+#[derive(Component)]
+struct Bullet;
+
+// Which side a bullet, or anything it can hit, is on
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum Faction {
+	Player,
+	Enemy,
+}
+
+impl Faction {
+	// Who can hurt whom. Collisions ask this rather than checking for
+	// particular marker components, so a new kind of bullet or target only
+	// needs a Faction.
+	fn damages(self, target: Faction) -> bool {
+		match (self, target) {
+			(Faction::Player, Faction::Enemy) => true,
+			(Faction::Enemy, Faction::Player) => true,
+			(Faction::Player, Faction::Player) => false,
+			(Faction::Enemy, Faction::Enemy) => false,
+		}
+	}
+
+	// Whether this side's bullets stop the other side's when they meet. As in
+	// the arcade game, an alien shot stops the player's and keeps falling, but
+	// the player can't shoot theirs down. A side's own bullets pass through
+	// each other.
+	fn blocks(self, other: Faction) -> bool {
+		match (self, other) {
+			(Faction::Player, Faction::Enemy) => false,
+			(Faction::Enemy, Faction::Player) => true,
+			(Faction::Player, Faction::Player) => false,
+			(Faction::Enemy, Faction::Enemy) => false,
+		}
+	}
+
+	// Bullets are tinted by who fired them, so it's clear which ones to dodge
+	fn bullet_colour(self, theme: &Theme, settings: &Settings) -> Color {
+		let colour = match self {
+			Faction::Player => theme.bullet,
+			Faction::Enemy => theme.enemy,
+		};
+
+		glow_colour(colour, settings)
+	}
+}
+
+#[derive(Component, Deref, DerefMut)]
+struct Velocity(Vec2);
+
+// Radians per second around z
+#[derive(Component)]
+struct AngularVelocity(f32);
+
+#[derive(Component)]
+struct Pickup {
+	value: u32,
+	timer: Timer,
+}
+
+#[derive(Component)]
+struct Debris {
+	timer: Timer,
+}
+
+// Where something was at the previous fixed tick, so it can be drawn part of
+// the way between there and where it is now
+#[derive(Component)]
+struct PreviousTransform(Vec3);
+
+// An after-image left behind a bullet. Only for looks, it has no Collider.
+#[derive(Component)]
+struct TrailSegment {
+	// The bullet that left it, the segment goes when the bullet does
+	bullet: Entity,
+	timer: Timer,
+}
+
+// Added on top of where something is drawn, without moving it in the game
+#[derive(Component, Default)]
+struct CosmeticOffset {
+	translation: Vec3,
+	// Radians around z
+	rotation: f32,
+}
+
+// How long the player ship is still kicked back from its last shot
+#[derive(Component)]
+struct Recoil(Timer);
+
+impl Default for Recoil {
+	fn default() -> Self {
+		let mut timer = Timer::from_seconds(RECOIL_SECS, TimerMode::Once);
+		// Not recoiling until the first shot
+		timer.tick(timer.duration());
+		Recoil(timer)
+	}
+}
+
+#[derive(Component)]
+struct MuzzleFlash {
+	timer: Timer,
+}
+
+// One of the arrows warning which wall the formation is about to hit
+#[derive(Component)]
+struct DescentArrow {
+	// Same as FormationParams::direction, -1 for the left wall and 1 for the right
+	side: f32,
+}
+
+#[derive(Component)]
+struct Collider {
+	half_extents: Vec2,
+}
+
+impl Collider {
+	fn size(&self) -> Vec2 {
+		self.half_extents * 2.0
+	}
+}
+
+#[derive(Default)]
+struct CollisionEvent;
+
+// Something that damages the player touched them
+#[derive(Default)]
+struct PlayerHitEvent;
+
+// The player wants to shoot. fire_weapon decides if they can.
+#[derive(Default)]
+struct FireIntent;
+
+// A shot actually left the ship
+struct ShotFired {
+	position: Vec3,
+}
+
+// Asks for a sound to be played. audio_playback is the only thing that talks
+// to Audio, so every sound goes through SoundManager's limits.
+struct PlaySound {
+	sound: SoundId,
+	// 1 is the clip's own volume
+	volume: f32,
+	// Each play is sped up or slowed down by up to this much, so repeats
+	// don't all sound identical
+	pitch_variance: f32,
+}
+
+impl PlaySound {
+	fn new(sound: SoundId) -> Self {
+		PlaySound {
+			sound,
+			volume: 1.0,
+			pitch_variance: 0.0,
+		}
+	}
+
+	fn with_pitch_variance(mut self, pitch_variance: f32) -> Self {
+		self.pitch_variance = pitch_variance;
+		self
+	}
+
+	fn with_volume(mut self, volume: f32) -> Self {
+		self.volume = volume;
+		self
+	}
+}
+
+// Which clip to play. Enemies each have their own death sound.
+#[derive(Clone, Copy)]
+enum SoundId {
+	Shoot,
+	EnemyDeath(EnemyKind),
+	UiHover,
+	UiClick,
+	Fanfare,
+}
+
+impl SoundId {
+	// The SoundManager channel it's limited on
+	fn effect(self) -> SoundEffect {
+		match self {
+			SoundId::Shoot => SoundEffect::Shoot,
+			SoundId::EnemyDeath(_) => SoundEffect::EnemyHit,
+			SoundId::UiHover => SoundEffect::UiHover,
+			SoundId::UiClick => SoundEffect::UiClick,
+			SoundId::Fanfare => SoundEffect::Fanfare,
+		}
+	}
+
+	// Loud enough that the music should get out of its way. Bombs and boss
+	// deaths belong here too once there are any.
+	fn ducks_music(self) -> bool {
+		matches!(self, SoundId::Fanfare)
+	}
+}
+
+struct EnemyKilledEvent {
+	kind: EnemyKind,
+	position: Vec3,
+}
+
+// A line for the event log in the corner of the HUD. Any system can send one.
+struct GameMessage(String);
+
+#[derive(Component)]
+struct Enemy;
+
+// A killed enemy shows up white for a frame before it disappears
+#[derive(Component)]
+struct HitFlash {
+	shown: bool,
+}
+
+/// Everything on the main menu screen, despawned when it's left
+#[derive(Component)]
+pub struct Menu;
+
+// An enemy in the formation behind the main menu. It isn't an Enemy, so nothing
+// in the game can shoot it or score from it, but it marches the same way.
+#[derive(Component)]
+struct MenuDemo;
+
+/// Everything on the settings screen
+#[derive(Component)]
+pub struct SettingsMenu;
+
+/// Everything on the mutators screen
+#[derive(Component)]
+pub struct MutatorsMenu;
+
+#[derive(Component)]
+struct ChallengeMenu;
+
+// Asks before closing the window throws a run away
+#[derive(Component)]
+struct QuitDialog;
+
+#[derive(Component, Clone, Copy)]
+enum QuitDialogAction {
+	Quit,
+	Cancel,
+}
+
+// The code typed in so far
+#[derive(Component)]
+struct ChallengeInputText;
+
+#[derive(Component)]
+struct ChallengeErrorText;
+
+#[derive(Component, Clone, Copy)]
+enum ChallengeButtonAction {
+	Play,
+	Back,
+}
+
+#[derive(Component)]
+struct MutatorsHud;
+
+// The end of wave bonus tally in the middle of the screen
+#[derive(Component)]
+struct WaveTallyUi;
+
+#[derive(Component)]
+struct WaveBanner {
+	timer: Timer,
+}
+
+// Anything in the wave banner that fades in and out with it
+#[derive(Component)]
+struct WaveBannerPart;
+
+// The container the event log's lines are stacked in
+#[derive(Component)]
+struct EventLog;
+
+#[derive(Component)]
+struct EventLogLine {
+	timer: Timer,
+}
+
+// An enemy on its way into the formation at the start of a wave. It can't be
+// shot until it arrives, which is when it gets its Collider.
+#[derive(Component)]
+struct FlyingIn {
+	start: Vec3,
+	// The curve bends towards this
+	control: Vec3,
+	target: Vec3,
+	// 0 to 1
+	progress: f32,
+}
+
+// One dot of the Bullet Gravity aim preview
+#[derive(Component)]
+struct ArcPreviewDot;
+
+// Player bullets fired with the Ricochet mutator, and how many bounces they
+// have left
+#[derive(Component)]
+struct Ricochet {
+	bounces_left: u32,
+}
+
+// The text inside any menu button
+#[derive(Component)]
+struct ButtonLabel;
+
+/// What a main menu button does when it's clicked
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum MenuButtonAction {
+	Play,
+	Challenge,
+	Mutators,
+	Settings,
+}
+
+/// What a settings button does: changes the setting it's named after, or
+/// goes `Back` to the menu
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum SettingsButtonAction {
+	Theme,
+	Hitstop,
+	Crt,
+	CrtIntensity,
+	Bloom,
+	BloomIntensity,
+	EventLog,
+	Finisher,
+	FlyIn,
+	ReducedMotion,
+	WindowSize,
+	Monitor,
+	Language,
+	Assist,
+	Announcer,
+	MusicDucking,
+	Back,
+}
+
+impl SettingsButtonAction {
+	fn label(&self, settings: &Settings, strings: &Strings) -> String {
+		let toggle = |key: &str, value: bool| strings.format(key, &[strings.on_off(value)]);
+		let percent = |key: &str, value: f32| strings.format(key, &[&format!("{:.0}", value * 100.0)]);
+
+		match self {
+			SettingsButtonAction::Theme => {
+				strings.format("settings.theme", &[strings.get(settings.theme.name_key())])
+			},
+			SettingsButtonAction::Hitstop => toggle("settings.hitstop", settings.hitstop),
+			SettingsButtonAction::Crt => toggle("settings.crt", settings.crt),
+			SettingsButtonAction::CrtIntensity => percent("settings.crt_strength", settings.crt_intensity),
+			SettingsButtonAction::Bloom => toggle("settings.bloom", settings.bloom),
+			SettingsButtonAction::BloomIntensity => percent("settings.bloom_strength", settings.bloom_intensity),
+			SettingsButtonAction::EventLog => toggle("settings.event_log", settings.event_log),
+			SettingsButtonAction::Finisher => toggle("settings.finisher", settings.finisher),
+			SettingsButtonAction::FlyIn => toggle("settings.fly_in", settings.fly_in),
+			SettingsButtonAction::ReducedMotion => toggle("settings.reduced_motion", settings.reduced_motion),
+			SettingsButtonAction::WindowSize => {
+				strings.format("settings.window", &[&settings.window_size.name()])
+			},
+			SettingsButtonAction::Monitor => {
+				strings.format("settings.monitor", &[&(settings.monitor + 1).to_string()])
+			},
+			SettingsButtonAction::Language => strings.format("settings.language", &[settings.language.name()]),
+			SettingsButtonAction::Assist => toggle("settings.assist", settings.assist),
+			SettingsButtonAction::Announcer => toggle("settings.announcer", settings.announcer),
+			SettingsButtonAction::MusicDucking => toggle("settings.music_ducking", settings.music_ducking),
+			SettingsButtonAction::Back => strings.get("common.back").to_string(),
+		}
+	}
+}
+
+// Any text in the HUD bar, so theme changes can recolour it
+#[derive(Component)]
+struct HudText;
+
+#[derive(Component)]
+struct ScoreboardText;
+
+#[derive(Component)]
+struct WaveText;
+
+#[derive(Component)]
+struct CurrencyText;
+
+// The wave progress bar's background, hovering it shows how many are left
+#[derive(Component)]
+struct WaveProgressTrack;
+
+#[derive(Component)]
+struct WaveProgressFill;
+
+#[derive(Component)]
+struct WaveRemainingText;
+
+#[derive(Component)]
+struct HighScoreText;
+
+#[derive(Component)]
+struct DebugOverlay;
+
+#[derive(Component)]
+struct MainCamera;
+
+#[derive(Component)]
+struct CrtCamera;
+
+#[derive(Resource)]
+struct ShootingSound(Handle<AudioSource>);
+
+#[derive(Resource)]
+struct FanfareSound(Handle<AudioSource>);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Component, Reflect, FromReflect, Serialize, Deserialize)]
+enum EnemyKind {
+	Invader,
+}
+
+impl EnemyKind {
+	const ALL: [EnemyKind; 1] = [EnemyKind::Invader];
+}
+
+#[derive(Clone, Reflect, FromReflect, Serialize, Deserialize)]
+struct EnemyInfo {
+	name: String,
+	width: f32,
+	height: f32,
+	points: usize,
+	// Relative to the assets folder
+	death_sound: String,
+	#[serde(skip)]
+	#[reflect(ignore)]
+	death_sound_handle: Handle<AudioSource>,
+	// Each entry is rolled separately, so one kill can drop more than one
+	#[serde(default)]
+	drops: Vec<ScrapDrop>,
+}
+
+#[derive(Clone, Reflect, FromReflect, Serialize, Deserialize)]
+struct ScrapDrop {
+	value: u32,
+	// 0 to 1
+	chance: f32,
+}
+
+impl EnemyInfo {
+	fn size(&self) -> Vec2 {
+		Vec2::new(self.width, self.height)
+	}
+}
+
+// One formation in FORMATIONS_PATH, before it's been checked
+#[derive(Deserialize)]
+struct FormationEntry {
+	name: String,
+	// One string per row from the top, X for an enemy and . for a gap
+	layout: Vec<String>,
+}
+
+// Which slots of the grid a formation fills. Never empty, and never bigger than
+// ENEMY_ROWS x ENEMY_COLUMNS.
+#[derive(Clone)]
+struct FormationLayout {
+	cells: Vec<Vec<bool>>,
+}
+
+impl FormationLayout {
+	fn classic() -> Self {
+		FormationLayout {
+			cells: vec![vec![true; ENEMY_COLUMNS]; ENEMY_ROWS],
+		}
+	}
+
+	// Errors say which row and column is wrong, counting from 1
+	fn parse(name: &str, rows: &[String]) -> Result<Self, String> {
+		if rows.is_empty() || rows.len() > ENEMY_ROWS {
+			return Err(format!("{name} has {} rows, it needs 1 to {ENEMY_ROWS}", rows.len()));
+		}
+
+		let columns = rows[0].chars().count();
+		if columns == 0 || columns > ENEMY_COLUMNS {
+			return Err(format!("{name}, row 1: {columns} wide, it needs to be 1 to {ENEMY_COLUMNS}"));
+		}
+
+		let mut cells = Vec::with_capacity(rows.len());
+		for (row, line) in rows.iter().enumerate() {
+			let width = line.chars().count();
+			if width != columns {
+				return Err(format!("{name}, row {}: {width} wide, but row 1 is {columns}", row + 1));
+			}
+
+			let parsed = line
+				.chars()
+				.enumerate()
+				.map(|(column, cell)| match cell {
+					'X' => Ok(true),
+					'.' => Ok(false),
+					other => Err(format!("{name}, row {}, column {}: '{other}' isn't X or .", row + 1, column + 1)),
+				})
+				.collect::<Result<Vec<_>, _>>()?;
+			cells.push(parsed);
+		}
+
+		let layout = FormationLayout { cells };
+		if layout.count() == 0 {
+			return Err(format!("{name} has no enemies in it"));
+		}
+		Ok(layout)
+	}
+
+	fn columns(&self) -> usize {
+		self.cells[0].len()
+	}
+
+	fn count(&self) -> usize {
+		self.cells.iter().flatten().filter(|&&cell| cell).count()
+	}
+}
+
+// The formation shapes waves take turns using, loaded from FORMATIONS_PATH.
+// Broken ones are skipped with a warning rather than stopping the game.
+#[derive(Resource)]
+struct Formations {
+	layouts: Vec<FormationLayout>,
+}
+
+impl Formations {
+	fn load() -> Self {
+		let entries: Vec<FormationEntry> = match std::fs::read_to_string(FORMATIONS_PATH) {
+			Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+				warn!("Couldn't read {FORMATIONS_PATH}: {err}");
+				Vec::new()
+			}),
+			Err(err) => {
+				warn!("Couldn't open {FORMATIONS_PATH}: {err}");
+				Vec::new()
+			},
+		};
+
+		let mut layouts: Vec<FormationLayout> = entries
+			.iter()
+			.filter_map(|entry| {
+				FormationLayout::parse(&entry.name, &entry.layout)
+					.map_err(|err| warn!("Skipping a formation in {FORMATIONS_PATH}: {err}"))
+					.ok()
+			})
+			.collect();
+
+		if layouts.is_empty() {
+			warn!("No usable formations in {FORMATIONS_PATH}, every wave will be the classic grid");
+			layouts.push(FormationLayout::classic());
+		}
+
+		Formations { layouts }
+	}
+
+	// Waves go through the formations in order, then start again
+	fn for_wave(&self, wave: u32) -> &FormationLayout {
+		&self.layouts[(wave.max(1) - 1) as usize % self.layouts.len()]
+	}
+}
+
+// Everything that differs between kinds of enemy, loaded from ENEMY_CATALOG_PATH.
+// Adding a kind should only need a new EnemyKind variant and an entry there.
+#[derive(Resource, Clone, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+struct EnemyCatalog {
+	enemies: HashMap<EnemyKind, EnemyInfo>,
+}
+
+impl EnemyCatalog {
+	fn fallback(kind: EnemyKind) -> EnemyInfo {
+		match kind {
+			EnemyKind::Invader => EnemyInfo {
+				name: "Invader".to_string(),
+				width: ENEMY_SIZE.x,
+				height: ENEMY_SIZE.y,
+				points: 10,
+				death_sound: "audio/enemy_hit.wav".to_string(),
+				death_sound_handle: Handle::default(),
+				drops: vec![
+					ScrapDrop { value: 1, chance: 0.3 },
+					ScrapDrop { value: 5, chance: 0.05 },
+					ScrapDrop { value: 10, chance: 0.01 },
+				],
+			},
+		}
+	}
+
+	fn load() -> Self {
+		let mut catalog = match std::fs::read_to_string(ENEMY_CATALOG_PATH) {
+			Ok(contents) => ron::from_str(&contents).unwrap_or_else(|err| {
+				warn!("Couldn't read {ENEMY_CATALOG_PATH}, using the built-in enemies: {err}");
+				EnemyCatalog { enemies: HashMap::new() }
+			}),
+			Err(err) => {
+				warn!("Couldn't open {ENEMY_CATALOG_PATH}, using the built-in enemies: {err}");
+				EnemyCatalog { enemies: HashMap::new() }
+			},
+		};
+
+		for kind in EnemyKind::ALL {
+			let info = catalog.enemies.entry(kind).or_insert_with(|| {
+				warn!("{ENEMY_CATALOG_PATH} has no entry for {kind:?}, using the built-in one");
+				EnemyCatalog::fallback(kind)
+			});
+
+			if find_sound(&info.death_sound).is_none() {
+				warn!("{kind:?}'s death sound {} doesn't exist", info.death_sound);
+			}
+		}
+
+		catalog
+	}
+
+	fn get(&self, kind: EnemyKind) -> &EnemyInfo {
+		// load() fills in every kind, so this can't fail
+		&self.enemies[&kind]
+	}
+}
+
+#[derive(Resource)]
+struct UiSounds {
+	hover: Handle<AudioSource>,
+	click: Handle<AudioSource>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum SoundEffect {
+	Shoot,
+	EnemyHit,
+	UiHover,
+	UiClick,
+	Fanfare,
+}
+
+impl SoundEffect {
+	// How many of this sound can be playing at once
+	fn max_instances(self) -> usize {
+		match self {
+			SoundEffect::Shoot => 4,
+			SoundEffect::EnemyHit => 3,
+			SoundEffect::UiHover | SoundEffect::UiClick | SoundEffect::Fanfare => 1,
+		}
+	}
+
+	// Roughly how long the clip is, there's no way to ask Audio when it's finished
+	fn length_secs(self) -> f32 {
+		match self {
+			SoundEffect::Shoot => 0.35,
+			SoundEffect::EnemyHit => 0.1,
+			SoundEffect::UiHover => 0.04,
+			SoundEffect::UiClick => 0.08,
+			SoundEffect::Fanfare => 0.7,
+		}
+	}
+}
+
+// Keeps track of which sound effects are playing, so a row of enemies dying
+// together doesn't start a dozen copies of the same explosion
+#[derive(Resource, Default)]
+struct SoundManager {
+	playing: Vec<(SoundEffect, Timer)>,
+	// How far the music is ducked, 0 to 1. Ramps up then back down rather than
+	// jumping, so the volume change never clicks.
+	duck: f32,
+	// Still ramping up towards fully ducked
+	duck_attack: bool,
+}
+
+impl SoundManager {
+	// Returns whether it actually played
+	fn play(
+		&mut self,
+		effect: SoundEffect,
+		audio: &Audio,
+		source: &Handle<AudioSource>,
+		settings: PlaybackSettings,
+	) -> bool {
+		let mut instances = 0;
+
+		for (playing, timer) in &self.playing {
+			if *playing != effect {
+				continue;
+			}
+
+			if timer.elapsed_secs() < SOUND_DEDUP_SECS {
+				return false;
+			}
+			instances += 1;
+		}
+
+		if instances >= effect.max_instances() {
+			return false;
+		}
+
+		audio.play_with_settings(source.clone(), settings);
+		self.playing.push((effect, Timer::from_seconds(effect.length_secs(), TimerMode::Once)));
+		true
+	}
+
+	fn duck_music(&mut self) {
+		self.duck_attack = true;
+	}
+
+	// Attack up to fully ducked, then release back to nothing
+	fn update_duck(&mut self, delta_secs: f32, config: &GameConfig) {
+		if self.duck_attack {
+			self.duck = (self.duck + delta_secs / config.music_duck_attack_secs).min(1.0);
+			self.duck_attack = self.duck < 1.0;
+		} else {
+			self.duck = (self.duck - delta_secs / config.music_duck_release_secs).max(0.0);
+		}
+	}
+
+	// What to multiply the music's volume by
+	fn music_volume(&self, config: &GameConfig) -> f32 {
+		1.0 - config.music_duck_amount.clamp(0.0, 1.0) * self.duck
+	}
+}
+
+#[derive(Resource)]
+struct MusicAssets {
+	base: Handle<AudioSource>,
+	// Percussion and lead, faded in as things get tense
+	tension: Handle<AudioSource>,
+}
+
+// The music currently playing. Both layers are the same length and are started
+// in the same frame so they stay in time with each other.
+#[derive(Resource)]
+struct MusicLayers {
+	base: Handle<AudioSink>,
+	tension: Handle<AudioSink>,
+	// Smoothed, so the layer fades rather than jumping about
+	tension_volume: f32,
+}
+
+// The image the game is rendered into while the CRT effect is on, and the
+// material that draws it back out to the window
+#[derive(Resource)]
+struct CrtAssets {
+	image: Handle<Image>,
+	material: Handle<CrtMaterial>,
+}
+
+#[derive(AsBindGroup, TypeUuid, Clone)]
+#[uuid = "8da331a0-3d2b-41da-8a2c-6d56c205b2fc"]
+struct CrtMaterial {
+	#[uniform(0)]
+	intensity: f32,
+	#[texture(1)]
+	#[sampler(2)]
+	source: Handle<Image>,
+}
+
+impl Material2d for CrtMaterial {
+	fn fragment_shader() -> ShaderRef {
+		"shaders/crt.wgsl".into()
+	}
+}
+
+// Which file format each sound ended up being loaded from, for the debug
+// overlay and bug reports
+#[derive(Resource, Default)]
+struct AudioFormats {
+	formats: HashMap<String, &'static str>,
+}
+
+impl AudioFormats {
+	// Takes a path with or without an extension, and loads whichever of
+	// AUDIO_FORMATS exists first
+	fn load(&mut self, asset_server: &AssetServer, path: &str) -> Handle<AudioSource> {
+		let (found, format) = find_sound(path).unwrap_or_else(|| {
+			warn!("No sound found for {path}");
+			// Let the asset server report it as missing, the game carries on silently
+			let fallback = AUDIO_FORMATS[AUDIO_FORMATS.len() - 1];
+			(std::path::Path::new(path).with_extension(fallback).to_string_lossy().into_owned(), "missing")
+		});
+
+		self.formats.insert(path.to_string(), format);
+		asset_server.load(found)
+	}
+
+	// "ogg 2, wav 5"
+	fn summary(&self) -> String {
+		let mut counts: Vec<(&str, usize)> = Vec::new();
+		for &format in self.formats.values() {
+			match counts.iter_mut().find(|(name, _)| *name == format) {
+				Some((_, count)) => *count += 1,
+				None => counts.push((format, 1)),
+			}
+		}
+		counts.sort_unstable();
+
+		counts
+			.iter()
+			.map(|(format, count)| format!("{format} {count}"))
+			.collect::<Vec<_>>()
+			.join(", ")
+	}
+}
+
+// The first of AUDIO_FORMATS that exists for this sound, and its extension.
+// The web build can't look before it loads, so it uses the extension it was
+// given, or the last (most widely shipped) format if there isn't one.
+fn find_sound(path: &str) -> Option<(String, &'static str)> {
+	let path = std::path::Path::new(path);
+
+	if cfg!(target_arch = "wasm32") {
+		let format = path
+			.extension()
+			.and_then(|extension| extension.to_str())
+			.and_then(|extension| AUDIO_FORMATS.into_iter().find(|format| *format == extension))
+			.unwrap_or(AUDIO_FORMATS[AUDIO_FORMATS.len() - 1]);
+		return Some((path.with_extension(format).to_string_lossy().into_owned(), format));
+	}
+
+	AUDIO_FORMATS.into_iter().find_map(|format| {
+		let candidate = path.with_extension(format);
+		std::path::Path::new("assets")
+			.join(&candidate)
+			.exists()
+			.then(|| (candidate.to_string_lossy().into_owned(), format))
+	})
+}
+
+// Every bullet shares one sprite image. Bullets are tinted per-sprite, so
+// changing the theme retints the ones already in flight. The mesh and material
+// are only used if the sprite fails to load.
+#[derive(Resource)]
+struct BulletAssets {
+	sprite: Handle<Image>,
+	mesh: Mesh2dHandle,
+	material: Handle<ColorMaterial>,
+}
+
+// The player ship's frames in one sheet. If the sheet fails to load the ship
+// falls back to the old single image, which just doesn't bank.
+#[derive(Resource)]
+struct PlayerAssets {
+	sprite: Handle<Image>,
+	sheet: Handle<Image>,
+	atlas: Handle<TextureAtlas>,
+}
+
+impl PlayerAssets {
+	const SHEET_PATH: &'static str = "sprites/player_atlas.png";
+	const FRAME_SIZE: Vec2 = Vec2::splat(PLAYER_SPRITE_SIZE);
+	// Frames go left to right. The last one is a white hit flash, for when the
+	// ship can take damage.
+	const COLUMNS: usize = 4;
+	const IDLE_FRAME: usize = 0;
+	const BANK_LEFT_FRAME: usize = 1;
+	const BANK_RIGHT_FRAME: usize = 2;
+
+	fn sheet_failed(&self, asset_server: &AssetServer) -> bool {
+		asset_server.get_load_state(&self.sheet) == LoadState::Failed
+	}
+}
+
+/// The score for the current run, after mutator and assist multipliers
+#[derive(Resource, Debug)]
+pub struct Scoreboard {
+	/// Points so far this run
+	pub score: usize,
+}
+
+// Scrap collected from pickups, for spending in the upgrade shop. Kept between
+// runs, but not saved.
+#[derive(Resource, Default)]
+struct Currency(u32);
+
+// Cheap randomness for gameplay rolls like pickup drops. Reseeded from RunCode
+// at the start of every run, so a challenge code plays out the same each time.
+#[derive(Resource)]
+struct GameRng(u64);
+
+impl Default for GameRng {
+	fn default() -> Self {
+		GameRng::from_seed(clock_seed())
+	}
+}
+
+impl GameRng {
+	fn from_seed(seed: u32) -> Self {
+		// Spread the seed's bits out, and xorshift gets stuck on 0
+		GameRng((seed as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+	}
+
+	// 0 to 1
+	fn next_f32(&mut self) -> f32 {
+		self.0 ^= self.0 << 13;
+		self.0 ^= self.0 >> 7;
+		self.0 ^= self.0 << 17;
+		(self.0 >> 40) as f32 / (1u64 << 24) as f32
+	}
+}
+
+fn clock_seed() -> u32 {
+	std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map_or(1, |duration| duration.as_nanos() as u32)
+}
+
+// The current run's seed and options, for replaying it. Set at the start of
+// every run.
+#[derive(Resource, Default)]
+struct RunCode(Option<ChallengeCode>);
+
+// GameConfig's own difficulty while a challenge code's is in use, put back at
+// the main menu
+#[derive(Resource, Default)]
+struct SavedDifficulty(Option<f32>);
+
+// Set while playing a run from a challenge code. Its options win over the
+// player's own until they're back at the main menu.
+#[derive(Resource, Default)]
+struct ActiveChallenge(Option<ChallengeCode>);
+
+// What's been typed on the challenge screen
+#[derive(Resource, Default)]
+struct ChallengeInput {
+	text: String,
+	error: Option<ChallengeError>,
+	// Enter was pressed, handled along with the Play button
+	submitted: bool,
+}
+
+// Best score since the game was opened
+#[derive(Resource, Default)]
+struct HighScore {
+	score: usize,
+	// Set with assist mode on, so it's marked as such
+	assisted: bool,
+	// The code, if it was set on a challenge run
+	challenge: Option<String>,
+}
+
+impl HighScore {
+	fn label(&self, strings: &Strings) -> String {
+		let score = format_thousands(self.score);
+
+		// The code already says whether assist was on
+		if let Some(code) = &self.challenge {
+			return strings.format("hud.high_score_challenge", &[&score, code]);
+		}
+
+		let key = if self.assisted { "hud.high_score_assisted" } else { "hud.high_score" };
+		strings.format(key, &[&score])
+	}
+}
+
+// Makes the game easier for anyone who wants a gentler start, at the cost of
+// score. The multipliers stack on top of GameConfig's difficulty.
+#[derive(Resource, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+struct AssistSettings {
+	// Follows Settings::assist
+	enabled: bool,
+	formation_speed: f32,
+	score_multiplier: f32,
+}
+
+impl Default for AssistSettings {
+	fn default() -> Self {
+		AssistSettings {
+			enabled: false,
+			formation_speed: 0.75,
+			score_multiplier: 0.5,
+		}
+	}
+}
+
+impl AssistSettings {
+	fn formation_speed_multiplier(&self) -> f32 {
+		if self.enabled { self.formation_speed } else { 1.0 }
+	}
+
+	fn score_multiplier(&self) -> f32 {
+		if self.enabled { self.score_multiplier } else { 1.0 }
+	}
+}
+
+/// Difficulty tuning, all in one place so it can be tweaked from the inspector
+#[derive(Resource, Debug, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+pub struct GameConfig {
+	/// Blend moving things between fixed ticks so they don't judder on high refresh
+	/// rate screens. Turn off to see exactly where the simulation has them.
+	pub interpolate_transforms: bool,
+	/// Scales every per-wave increase, 1 is normal
+	pub difficulty: f32,
+	/// March speed on the first wave
+	pub formation_speed: f32,
+	/// Added to the march speed for each wave after the first
+	pub formation_speed_per_wave: f32,
+	/// The march speed is multiplied by this each time the formation drops
+	pub formation_speed_per_drop: f32,
+	/// With every enemy but one dead, the march is this many times faster
+	pub formation_last_enemy_speedup: f32,
+	/// Nothing makes the march faster than this
+	pub formation_max_speed: f32,
+	/// How far the formation drops each time it reaches a wall
+	pub formation_drop: f32,
+	/// Each wave starts this much lower than the one before
+	pub formation_start_drop_per_wave: f32,
+	/// The most a wave can start below the usual spot
+	pub formation_max_start_drop: f32,
+	/// Pickups this close to the player fly to it. 0 until there's an upgrade for it.
+	pub pickup_magnet_radius: f32,
+	/// How much the player ship's art is scaled up. Tiny Player halves it.
+	pub player_scale: f32,
+	/// Kills within streak_rampage_secs of each other for a Rampage
+	pub streak_rampage_kills: usize,
+	/// How close together a Rampage's kills have to be
+	pub streak_rampage_secs: f32,
+	/// Points for a Rampage, before multipliers
+	pub streak_rampage_bonus: usize,
+	/// Kills within streak_annihilation_secs of each other for an Annihilation
+	pub streak_annihilation_kills: usize,
+	/// How close together an Annihilation's kills have to be
+	pub streak_annihilation_secs: f32,
+	/// Points for an Annihilation, before multipliers
+	pub streak_annihilation_bonus: usize,
+	/// How much of the music's volume is taken away while it's fully ducked
+	pub music_duck_amount: f32,
+	/// How long the music takes to dip when a big sound plays
+	pub music_duck_attack_secs: f32,
+	/// How long it takes to come back up afterwards
+	pub music_duck_release_secs: f32,
+}
+
+impl Default for GameConfig {
+	fn default() -> Self {
+		GameConfig {
+			interpolate_transforms: true,
+			difficulty: 1.0,
+			formation_speed: 40.0,
+			formation_speed_per_wave: 8.0,
+			formation_speed_per_drop: 1.05,
+			formation_last_enemy_speedup: 4.0,
+			formation_max_speed: 320.0,
+			formation_drop: 20.0,
+			formation_start_drop_per_wave: 15.0,
+			formation_max_start_drop: 150.0,
+			pickup_magnet_radius: 0.0,
+			player_scale: 2.0,
+			streak_rampage_kills: 5,
+			streak_rampage_secs: 2.0,
+			streak_rampage_bonus: 250,
+			streak_annihilation_kills: 10,
+			streak_annihilation_secs: 4.0,
+			streak_annihilation_bonus: 1000,
+			music_duck_amount: 0.4,
+			music_duck_attack_secs: 0.05,
+			music_duck_release_secs: 0.8,
+		}
+	}
+}
+
+// Stats for the current wave, reset when the next one starts
+#[derive(Resource, Default)]
+struct RunStats {
+	shots_hit: u32,
+	// Bullets that left the screen without hitting anything
+	shots_missed: u32,
+	// When each recent kill happened, oldest first. Only kept for as long as
+	// the longest streak window.
+	recent_kills: VecDeque<f32>,
+	// The best streak announced since the kills last dried up
+	streak: Option<KillStreak>,
+}
+
+// Lots of kills close together. Higher tiers are later in the list.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum KillStreak {
+	Rampage,
+	Annihilation,
+}
+
+impl KillStreak {
+	const ALL: [KillStreak; 2] = [KillStreak::Rampage, KillStreak::Annihilation];
+
+	// Kills, within how many seconds, and the bonus for them
+	fn rule(self, config: &GameConfig) -> (usize, f32, usize) {
+		match self {
+			KillStreak::Rampage => (
+				config.streak_rampage_kills,
+				config.streak_rampage_secs,
+				config.streak_rampage_bonus,
+			),
+			KillStreak::Annihilation => (
+				config.streak_annihilation_kills,
+				config.streak_annihilation_secs,
+				config.streak_annihilation_bonus,
+			),
+		}
+	}
+
+	fn name_key(self) -> &'static str {
+		match self {
+			KillStreak::Rampage => "streak.rampage",
+			KillStreak::Annihilation => "streak.annihilation",
+		}
+	}
+}
+
+impl RunStats {
+	// Only counts shots that have landed or left the screen, anything still in
+	// the air when the wave ends doesn't count either way
+	fn accuracy(&self) -> Option<f32> {
+		let resolved = self.shots_hit + self.shots_missed;
+		if resolved == 0 {
+			return None;
+		}
+
+		Some(self.shots_hit as f32 / resolved as f32)
+	}
+
+	fn bonuses(&self, strings: &Strings) -> Vec<BonusLine> {
+		let mut bonuses = Vec::new();
+
+		if let Some(accuracy) = self.accuracy() {
+			let percent = (accuracy * 100.0).floor();
+
+			if self.shots_missed == 0 {
+				bonuses.push(BonusLine {
+					label: strings.get("tally.perfect_accuracy").to_string(),
+					points: WAVE_PERFECT_ACCURACY_BONUS,
+					fanfare: true,
+				});
+			} else if accuracy >= WAVE_ACCURACY_BONUS_THRESHOLD {
+				bonuses.push(BonusLine {
+					label: strings.format("tally.accuracy", &[&percent.to_string()]),
+					points: WAVE_ACCURACY_BONUS,
+					fanfare: false,
+				});
+			}
+		}
+
+		bonuses
+	}
+}
+
+struct BonusLine {
+	label: String,
+	points: usize,
+	fanfare: bool,
+}
+
+// Counts through the end of wave bonuses one line at a time. The next wave
+// waits for it to finish.
+#[derive(Resource, Default)]
+struct WaveTally {
+	lines: Vec<BonusLine>,
+	shown: usize,
+	timer: Option<Timer>,
+	finished: bool,
+}
+
+#[derive(Resource)]
+struct Wave {
+	number: u32,
+	// Every enemy the wave spawned with. Only set when a formation spawns, so
+	// nothing later can make the progress bar go backwards.
+	total: usize,
+	killed: usize,
+}
+
+impl Default for Wave {
+	fn default() -> Self {
+		Wave {
+			number: 1,
+			total: 0,
+			killed: 0,
+		}
+	}
+}
+
+impl Wave {
+	fn remaining(&self) -> usize {
+		self.total.saturating_sub(self.killed)
+	}
+
+	// 0 to 1
+	fn progress(&self) -> f32 {
+		if self.total == 0 {
+			return 0.0;
+		}
+		(self.killed as f32 / self.total as f32).min(1.0)
+	}
+}
+
+// How the current wave's formation moves. Set up at the start of each wave
+// and sped up by march_formation every time it drops.
+#[derive(Resource, Clone, Copy, Reflect)]
+#[reflect(Resource)]
+struct FormationParams {
+	// 1 for right and -1 for left
+	direction: f32,
+	speed: f32,
+	// How far below the usual spot this wave's formation starts
+	start_drop: f32,
+	// The speed it's actually marching at, once the fewer-enemies speedup is
+	// applied. Only here to be read in the inspector.
+	effective_speed: f32,
+	// How many enemies it started with, set by spawn_formation
+	enemies: usize,
+	// Seconds until it next hits a wall and drops, worked out by march_formation
+	// every tick from where it is and how fast it's going right now. Infinite
+	// while it isn't marching.
+	time_to_descent: f32,
+	// With the Wrap mutator, how far it's marched since it last dropped
+	lap_distance: f32,
+}
+
+impl Default for FormationParams {
+	fn default() -> Self {
+		FormationParams::for_wave(1, &GameConfig::default())
+	}
+}
+
+impl FormationParams {
+	fn for_wave(wave: u32, config: &GameConfig) -> Self {
+		let waves_in = (wave - 1) as f32 * config.difficulty;
+		let speed = (config.formation_speed + waves_in * config.formation_speed_per_wave)
+			.min(config.formation_max_speed);
+
+		FormationParams {
+			direction: 1.0,
+			speed,
+			start_drop: (waves_in * config.formation_start_drop_per_wave).min(config.formation_max_start_drop),
+			effective_speed: speed,
+			// The full grid, until spawn_formation says otherwise
+			enemies: ENEMY_ROWS * ENEMY_COLUMNS,
+			time_to_descent: f32::INFINITY,
+			lap_distance: 0.0,
+		}
+	}
+
+	// Called each time the formation reaches a wall and drops down
+	fn drop(&mut self, config: &GameConfig) {
+		self.direction = -self.direction;
+		self.speed_up(config);
+	}
+
+	fn speed_up(&mut self, config: &GameConfig) {
+		self.speed = (self.speed * config.formation_speed_per_drop).min(config.formation_max_speed);
+	}
+
+	// The fewer enemies are left the faster they go. The cap applies to the
+	// final speed so the two ramps can't stack past it, assist mode and any
+	// temporary modifiers change it after that.
+	fn speed_with_survivors(
+		&self,
+		alive: usize,
+		config: &GameConfig,
+		assist: &AssistSettings,
+		modifiers: &FormationModifiers,
+	) -> f32 {
+		// A formation of one is as fast as it gets from the start
+		let total = self.enemies.max(2) as f32;
+		let killed = 1.0 - (alive.max(1) - 1) as f32 / (total - 1.0);
+		let speedup = 1.0 + (config.formation_last_enemy_speedup - 1.0) * killed;
+
+		(self.speed * speedup).min(config.formation_max_speed)
+			* assist.formation_speed_multiplier()
+			* modifiers.multiplier()
+	}
+}
+
+// A temporary change to the formation's speed, like a breather after a boss or
+// a power-up that slows the invaders. Eased ones blend back to normal over
+// their duration instead of stopping all at once.
+struct FormationModifier {
+	multiplier: f32,
+	timer: Timer,
+	ease_out: bool,
+}
+
+impl FormationModifier {
+	fn current(&self) -> f32 {
+		if self.ease_out {
+			self.multiplier + (1.0 - self.multiplier) * self.timer.percent()
+		} else {
+			self.multiplier
+		}
+	}
+}
+
+// Every active FormationModifier. They multiply together, so two halvings make
+// a quarter, and each one drops off when its timer runs out.
+#[derive(Resource, Default)]
+struct FormationModifiers {
+	active: Vec<FormationModifier>,
+}
+
+impl FormationModifiers {
+	// Only the console pushes modifiers until boss waves and power-ups exist
+	#[cfg_attr(not(feature = "devtools"), allow(dead_code))]
+	fn push(&mut self, multiplier: f32, secs: f32, ease_out: bool) {
+		self.active.push(FormationModifier {
+			multiplier,
+			timer: Timer::from_seconds(secs, TimerMode::Once),
+			ease_out,
+		});
+	}
+
+	fn multiplier(&self) -> f32 {
+		self.active.iter().map(FormationModifier::current).product()
+	}
+
+	fn tick(&mut self, delta: std::time::Duration) {
+		for modifier in &mut self.active {
+			modifier.timer.tick(delta);
+		}
+		self.active.retain(|modifier| !modifier.timer.finished());
+	}
+}
+
+// What the scoreboard is currently showing, which counts up to the real score
+// rather than jumping straight to it
+#[derive(Resource, Default)]
+struct DisplayedScore {
+	value: f32,
+	target: usize,
+	// Points per second, worked out whenever the target changes
+	rate: f32,
+}
+
+/// Gameplay modifiers picked on the Mutators screen before a run. They stack,
+/// and some change how much each point is worth.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Reflect)]
+#[reflect(Resource)]
+pub struct Mutators {
+	/// Player bullets arc back down
+	pub bullet_gravity: bool,
+	/// Half-size ship and hitbox, for a score bonus
+	pub tiny_player: bool,
+	/// Player bullets bounce once off the side walls
+	pub ricochet: bool,
+	/// The player defends from the top and the invaders come up from below
+	pub mirror: bool,
+	/// The formation marches off one side and back on the other, and only drops
+	/// once per lap
+	pub wrap: bool,
+}
+
+impl Mutators {
+	fn score_multiplier(&self) -> f32 {
+		let mut multiplier = 1.0;
+
+		if self.tiny_player {
+			multiplier *= TINY_PLAYER_SCORE_MULTIPLIER;
+		}
+		if self.wrap {
+			multiplier *= WRAP_SCORE_MULTIPLIER;
+		}
+
+		multiplier
+	}
+
+	// As string keys
+	fn active_names(&self) -> Vec<&'static str> {
+		[
+			(self.bullet_gravity, "mutators.bullet_gravity"),
+			(self.tiny_player, "mutators.tiny_player"),
+			(self.ricochet, "mutators.ricochet"),
+			(self.mirror, "mutators.mirror"),
+			(self.wrap, "mutators.wrap"),
+		]
+		.into_iter()
+		.filter(|(active, _)| *active)
+		.map(|(_, name)| name)
+		.collect()
+	}
+
+	fn player_scale(&self, config: &GameConfig) -> f32 {
+		if self.tiny_player { config.player_scale / 2.0 } else { config.player_scale }
+	}
+}
+
+// Which way is "up" from the player's point of view: the way they shoot, and
+// the opposite of the way the invaders advance. Everything that cares about
+// direction goes through this rather than assuming +y, so Mirror mode can flip
+// the whole playfield.
+#[derive(Resource, Clone, Copy)]
+struct PlayfieldOrientation {
+	up: Vec2,
+}
+
+impl Default for PlayfieldOrientation {
+	fn default() -> Self {
+		PlayfieldOrientation {
+			up: Vec2::Y,
+		}
+	}
+}
+
+impl PlayfieldOrientation {
+	fn from_mutators(mutators: &Mutators) -> Self {
+		PlayfieldOrientation {
+			up: if mutators.mirror { Vec2::NEG_Y } else { Vec2::Y },
+		}
+	}
+
+	// Turns a y laid out for the normal orientation into a world y
+	fn world_y(&self, y: f32) -> f32 {
+		y * self.up.y
+	}
+
+	// How far "up" the playfield a world position is
+	fn height_of(&self, position: Vec3) -> f32 {
+		position.truncate().dot(self.up)
+	}
+}
+
+/// What a mutators button does: toggles the mutator it's named after, or goes
+/// `Back` to the menu
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+pub enum MutatorButtonAction {
+	BulletGravity,
+	TinyPlayer,
+	Ricochet,
+	Mirror,
+	Wrap,
+	Back,
+}
+
+impl MutatorButtonAction {
+	fn label(&self, mutators: &Mutators, strings: &Strings) -> String {
+		let toggle = |key: &str, value: bool| strings.format(key, &[strings.on_off(value)]);
+
+		match self {
+			MutatorButtonAction::BulletGravity => toggle("mutators.bullet_gravity_button", mutators.bullet_gravity),
+			MutatorButtonAction::TinyPlayer => toggle("mutators.tiny_player_button", mutators.tiny_player),
+			MutatorButtonAction::Ricochet => toggle("mutators.ricochet_button", mutators.ricochet),
+			MutatorButtonAction::Mirror => toggle("mutators.mirror_button", mutators.mirror),
+			MutatorButtonAction::Wrap => toggle("mutators.wrap_button", mutators.wrap),
+			MutatorButtonAction::Back => strings.get("common.back").to_string(),
+		}
+	}
+}
+
+// Everything needed to play a run again: the seed plus every option that
+// changes the game
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct ChallengeCode {
+	seed: u32,
+	assist: bool,
+	mutators: Mutators,
+	// GameConfig::difficulty, stored to the nearest tenth
+	difficulty: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ChallengeError {
+	Length,
+	Character,
+	Checksum,
+	Version,
+}
+
+impl ChallengeError {
+	fn key(&self) -> &'static str {
+		match self {
+			ChallengeError::Length => "challenge.error_length",
+			ChallengeError::Character => "challenge.error_character",
+			ChallengeError::Checksum => "challenge.error_checksum",
+			ChallengeError::Version => "challenge.error_version",
+		}
+	}
+}
+
+impl ChallengeCode {
+	// Version, seed, option flags, difficulty and a checksum, 8 bytes in all
+	fn to_bytes(self) -> [u8; 8] {
+		let flags = [
+			self.assist,
+			self.mutators.bullet_gravity,
+			self.mutators.tiny_player,
+			self.mutators.ricochet,
+			self.mutators.mirror,
+			self.mutators.wrap,
+		]
+		.iter()
+		.enumerate()
+		.fold(0u8, |flags, (bit, &set)| flags | ((set as u8) << bit));
+
+		let mut bytes = [0; 8];
+		bytes[0] = CHALLENGE_VERSION;
+		bytes[1..5].copy_from_slice(&self.seed.to_le_bytes());
+		bytes[5] = flags;
+		bytes[6] = (self.difficulty * 10.0).round().clamp(0.0, 255.0) as u8;
+		bytes[7] = challenge_checksum(&bytes[..7]);
+		bytes
+	}
+
+	fn encode(self) -> String {
+		challenge_base32(&self.to_bytes())
+	}
+
+	fn decode(code: &str) -> Result<Self, ChallengeError> {
+		let code = code.trim().to_ascii_uppercase();
+		if code.len() == CHALLENGE_V1_CODE_LEN {
+			return Err(ChallengeError::Version);
+		}
+		if code.len() != CHALLENGE_CODE_LEN {
+			return Err(ChallengeError::Length);
+		}
+
+		let mut bytes = Vec::with_capacity(8);
+		let mut buffer = 0u32;
+		let mut bits = 0;
+
+		for character in code.bytes() {
+			let value = CHALLENGE_ALPHABET
+				.iter()
+				.position(|&letter| letter == character)
+				.ok_or(ChallengeError::Character)?;
+
+			buffer = (buffer << 5) | value as u32;
+			bits += 5;
+
+			if bits >= 8 {
+				bits -= 8;
+				bytes.push((buffer >> bits) as u8);
+				buffer &= (1 << bits) - 1;
+			}
+		}
+
+		// The checksum goes first: a typo is far more likely than an old code
+		if bytes[7] != challenge_checksum(&bytes[..7]) {
+			return Err(ChallengeError::Checksum);
+		}
+		if bytes[0] != CHALLENGE_VERSION {
+			return Err(ChallengeError::Version);
+		}
+
+		let flag = |bit: u8| bytes[5] & (1 << bit) != 0;
+		Ok(ChallengeCode {
+			seed: u32::from_le_bytes([bytes[1], bytes[2], bytes[3], bytes[4]]),
+			assist: flag(0),
+			mutators: Mutators {
+				bullet_gravity: flag(1),
+				tiny_player: flag(2),
+				ricochet: flag(3),
+				mirror: flag(4),
+				wrap: flag(5),
+			},
+			difficulty: bytes[6] as f32 / 10.0,
+		})
+	}
+}
+
+// Five bits to a character, with the last one padded out
+fn challenge_base32(bytes: &[u8]) -> String {
+	let mut code = String::with_capacity(CHALLENGE_CODE_LEN);
+	let mut buffer = 0u32;
+	let mut bits = 0;
+
+	for &byte in bytes {
+		buffer = (buffer << 8) | byte as u32;
+		bits += 8;
+
+		while bits >= 5 {
+			bits -= 5;
+			code.push(CHALLENGE_ALPHABET[(buffer >> bits) as usize & 31] as char);
+		}
+		buffer &= (1 << bits) - 1;
+	}
+
+	// Pad the last few bits out to a whole character
+	if bits > 0 {
+		code.push(CHALLENGE_ALPHABET[(buffer << (5 - bits)) as usize & 31] as char);
+	}
+
+	code
+}
+
+// Rotating keeps swapped characters from adding up to the same thing
+fn challenge_checksum(bytes: &[u8]) -> u8 {
+	bytes.iter().fold(0x5A, |sum: u8, &byte| sum.rotate_left(3) ^ byte)
+}
+
+#[derive(Resource, Deref, DerefMut)]
+struct DebugOverlayTimer(Timer);
+
+// Set as soon as any cheat is used, so the run's score and achievements
+// aren't recorded.
+#[derive(Resource, Default)]
+struct TaintedRun(bool);
+
+// When enabled, nothing can hurt the player. Only reachable through the devtools.
+#[derive(Resource, Default)]
+struct GodMode(bool);
+
+// Half the size of the player ship as drawn, from its image and scale. Keeps
+// the ship on screen and tells bullets where its nose is.
+#[derive(Resource, Default)]
+struct PlayerBounds {
+	half_extents: Vec2,
+}
+
+// The size and desktop position of every connected monitor, in the order winit
+// lists them
+#[derive(Resource, Default)]
+struct Monitors {
+	sizes: Vec<UVec2>,
+	positions: Vec<IVec2>,
+}
+
+impl Monitors {
+	// Keeps a window of window_size that was at position on whichever monitor
+	// its top-left corner is on. None if that monitor isn't there any more.
+	fn clamp_window(&self, position: IVec2, window_size: UVec2) -> Option<IVec2> {
+		let (&origin, &size) = self.positions.iter().zip(&self.sizes).find(|(origin, size)| {
+			let offset = position - **origin;
+			offset.cmpge(IVec2::ZERO).all() && offset.cmplt(size.as_ivec2()).all()
+		})?;
+
+		let furthest = origin + (size.as_ivec2() - window_size.as_ivec2()).max(IVec2::ZERO);
+		Some(position.clamp(origin, furthest))
+	}
+}
+
+// How fast the fixed tick runs compared to normal, 1 is full speed
+#[derive(Resource)]
+struct GameSpeed(f32);
+
+impl Default for GameSpeed {
+	fn default() -> Self {
+		GameSpeed(1.0)
+	}
+}
+
+// The slow motion after the last enemy of a wave dies. The next wave waits
+// until it's over.
+#[derive(Resource, Default)]
+struct Finisher {
+	timer: Option<Timer>,
+	// Where the last enemy died, which the camera zooms towards
+	focus: Vec2,
+	// Where the camera was before the zoom, put back exactly afterwards
+	camera_home: Option<Transform>,
+}
+
+#[derive(Resource, Default)]
+struct Hitstop {
+	// Ticks of freeze still to come
+	ticks: u32,
+	// Whether this tick is frozen
+	frozen: bool,
+}
+
+// Player-facing options, saved to SETTINGS_PATH whenever they change.
+// Missing fields fall back to their defaults so old settings files still load.
+#[derive(Resource, Reflect, Serialize, Deserialize)]
+#[reflect(Resource)]
+#[serde(default)]
+struct Settings {
+	theme: ThemeKind,
+	// Some players find the freeze on kills nauseating
+	hitstop: bool,
+	crt: bool,
+	// 0 to 1
+	crt_intensity: f32,
+	bloom: bool,
+	// 0 to 1
+	bloom_intensity: f32,
+	// The fading log of kills and events in the HUD corner
+	event_log: bool,
+	// Slow motion and a zoom when the last enemy of a wave dies
+	finisher: bool,
+	// New waves fly in rather than appearing in place
+	fly_in: bool,
+	// Turns off purely decorative movement, like the formation bobbing
+	reduced_motion: bool,
+	window_size: WindowSize,
+	// Index into the monitors winit reports
+	monitor: usize,
+	language: Language,
+	// Slower enemies for less score, see AssistSettings
+	assist: bool,
+	// Where the window was last left, on desktop. None to centre it.
+	window: Option<WindowState>,
+	// The banner and sting for kill streaks. The bonus is awarded either way.
+	announcer: bool,
+	// Big sounds like the wave fanfare briefly turn the music down
+	music_ducking: bool,
+}
+
+// The window's top-left corner in physical pixels, across every monitor
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+struct WindowState {
+	x: i32,
+	y: i32,
+}
+
+impl Default for Settings {
+	fn default() -> Self {
+		Settings {
+			theme: ThemeKind::default(),
+			hitstop: true,
+			crt: false,
+			crt_intensity: 0.5,
+			bloom: true,
+			bloom_intensity: 0.3,
+			event_log: true,
+			finisher: true,
+			fly_in: true,
+			reduced_motion: false,
+			window_size: WindowSize::default(),
+			monitor: 0,
+			language: Language::default(),
+			assist: false,
+			window: None,
+			announcer: true,
+			music_ducking: true,
+		}
+	}
+}
+
+impl Settings {
+	fn load() -> Self {
+		let Ok(contents) = std::fs::read_to_string(SETTINGS_PATH) else {
+			return Settings::default();
+		};
+
+		ron::from_str(&contents).unwrap_or_else(|err| {
+			warn!("Couldn't read {SETTINGS_PATH}, using default settings: {err}");
+			Settings::default()
+		})
+	}
+
+	fn save(&self) {
+		let contents = match ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default()) {
+			Ok(contents) => contents,
+			Err(err) => {
+				warn!("Couldn't serialise settings: {err}");
+				return;
+			},
+		};
+
+		if let Err(err) = std::fs::write(SETTINGS_PATH, contents) {
+			warn!("Couldn't save settings to {SETTINGS_PATH}: {err}");
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+enum ThemeKind {
+	#[default]
+	Classic,
+	HighContrast,
+	Deuteranopia,
+}
+
+impl ThemeKind {
+	fn name_key(&self) -> &'static str {
+		match self {
+			ThemeKind::Classic => "theme.classic",
+			ThemeKind::HighContrast => "theme.high_contrast",
+			ThemeKind::Deuteranopia => "theme.deuteranopia",
+		}
+	}
+
+	// For cycling through the themes in the settings menu
+	fn next(&self) -> Self {
+		match self {
+			ThemeKind::Classic => ThemeKind::HighContrast,
+			ThemeKind::HighContrast => ThemeKind::Deuteranopia,
+			ThemeKind::Deuteranopia => ThemeKind::Classic,
+		}
+	}
+
+	fn theme(&self) -> Theme {
+		match self {
+			ThemeKind::Classic => CLASSIC_THEME,
+			ThemeKind::HighContrast => HIGH_CONTRAST_THEME,
+			ThemeKind::Deuteranopia => DEUTERANOPIA_THEME,
+		}
+	}
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+enum Language {
+	#[default]
+	English,
+	French,
+}
+
+impl Language {
+	// The file in LANGUAGE_DIR its strings are in
+	fn code(&self) -> &'static str {
+		match self {
+			Language::English => "en",
+			Language::French => "fr",
+		}
+	}
+
+	// Always in the language itself, so it can be found from any other
+	fn name(&self) -> &'static str {
+		match self {
+			Language::English => "English",
+			Language::French => "Français",
+		}
+	}
+
+	fn next(&self) -> Self {
+		match self {
+			Language::English => Language::French,
+			Language::French => Language::English,
+		}
+	}
+}
+
+#[derive(Deserialize)]
+struct LanguageFile {
+	// For languages that need letters the default font doesn't have
+	#[serde(default)]
+	font: Option<String>,
+	strings: HashMap<String, String>,
+}
+
+impl LanguageFile {
+	fn load(language: Language) -> Option<Self> {
+		let path = format!("{LANGUAGE_DIR}/{}.ron", language.code());
+
+		let contents = match std::fs::read_to_string(&path) {
+			Ok(contents) => contents,
+			Err(err) => {
+				warn!("Couldn't open {path}: {err}");
+				return None;
+			},
+		};
+
+		ron::from_str(&contents)
+			.map_err(|err| warn!("Couldn't read {path}: {err}"))
+			.ok()
+	}
+}
+
+// Every piece of text the player sees, in their language. Anything missing
+// falls back to English, and failing that shows the key itself.
+#[derive(Resource)]
+struct Strings {
+	language: Language,
+	font: String,
+	strings: HashMap<String, String>,
+	fallback: HashMap<String, String>,
+}
+
+impl Strings {
+	fn load(language: Language) -> Self {
+		let fallback = LanguageFile::load(Language::English)
+			.map(|file| file.strings)
+			.unwrap_or_default();
+
+		let file = if language == Language::English {
+			None
+		} else {
+			LanguageFile::load(language)
+		};
+
+		let (font, strings) = match file {
+			Some(file) => (file.font, file.strings),
+			None => (None, HashMap::new()),
+		};
+
+		Strings {
+			language,
+			font: font.unwrap_or_else(|| UI_FONT.to_string()),
+			strings,
+			fallback,
+		}
+	}
+
+	fn get<'a>(&'a self, key: &'a str) -> &'a str {
+		self.strings
+			.get(key)
+			.or_else(|| self.fallback.get(key))
+			.map(String::as_str)
+			.unwrap_or(key)
+	}
+
+	// Fills in each "{}" in the string with the next value
+	fn format(&self, key: &str, values: &[&str]) -> String {
+		let mut parts = self.get(key).split("{}");
+		let mut formatted = parts.next().unwrap_or_default().to_string();
+
+		for (i, part) in parts.enumerate() {
+			formatted.push_str(values.get(i).copied().unwrap_or_default());
+			formatted.push_str(part);
+		}
+
+		formatted
+	}
+
+	fn on_off(&self, value: bool) -> &str {
+		self.get(if value { "common.on" } else { "common.off" })
+	}
+
+	fn font(&self, asset_server: &AssetServer) -> Handle<Font> {
+		asset_server.load(self.font.as_str())
+	}
+}
+
+// Windowed sizes the game can run at. The playfield is always WIDTH x HEIGHT,
+// bigger windows just draw it scaled up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, FromReflect, Serialize, Deserialize)]
+enum WindowSize {
+	#[default]
+	Hd,
+	HdPlus,
+	FullHd,
+}
+
+impl WindowSize {
+	// Biggest last, so the first one that doesn't fit a monitor ends the search
+	const ALL: [WindowSize; 3] = [WindowSize::Hd, WindowSize::HdPlus, WindowSize::FullHd];
+
+	fn size(&self) -> UVec2 {
+		match self {
+			WindowSize::Hd => UVec2::new(1280, 720),
+			WindowSize::HdPlus => UVec2::new(1600, 900),
+			WindowSize::FullHd => UVec2::new(1920, 1080),
+		}
+	}
+
+	fn name(&self) -> String {
+		let size = self.size();
+		format!("{}x{}", size.x, size.y)
+	}
+
+	fn next(&self) -> Self {
+		match self {
+			WindowSize::Hd => WindowSize::HdPlus,
+			WindowSize::HdPlus => WindowSize::FullHd,
+			WindowSize::FullHd => WindowSize::Hd,
+		}
+	}
+
+	fn fits(&self, monitor_size: UVec2) -> bool {
+		let size = self.size();
+		size.x <= monitor_size.x && size.y <= monitor_size.y
+	}
+}
+
+// The colours currently in use. Anything that spawns or tints something should
+// read from here rather than hard-coding a colour.
+#[derive(Resource, Clone, Copy, PartialEq)]
+struct Theme {
+	background: Color,
+	button: Color,
+	button_hovered: Color,
+	button_text: Color,
+	bullet: Color,
+	scoreboard: Color,
+	enemy: Color,
+}
+
+fn setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	mut enemy_catalog: ResMut<EnemyCatalog>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+	mut atlases: ResMut<Assets<TextureAtlas>>,
+) {
+		// Load the audio files and insert them into our resource
+	// This stops us having to load the file from disk everytime we want to play the sound.
+	let mut audio_formats = AudioFormats::default();
+
+	let shooting_sound = audio_formats.load(&asset_server, "audio/player_shoot");
+	commands.insert_resource(ShootingSound(shooting_sound));
+
+	commands.insert_resource(FanfareSound(audio_formats.load(&asset_server, "audio/wave_fanfare")));
+
+	for info in enemy_catalog.enemies.values_mut() {
+		info.death_sound_handle = audio_formats.load(&asset_server, &info.death_sound);
+	}
+
+	commands.insert_resource(UiSounds {
+		hover: audio_formats.load(&asset_server, "audio/ui_hover"),
+		click: audio_formats.load(&asset_server, "audio/ui_click"),
+	});
+
+	commands.insert_resource(MusicAssets {
+		base: audio_formats.load(&asset_server, "audio/music/gameplay_base"),
+		tension: audio_formats.load(&asset_server, "audio/music/gameplay_tension"),
+	});
+
+	info!("Loaded sounds as {}", audio_formats.summary());
+	commands.insert_resource(audio_formats);
+
+	let player_sheet = asset_server.load(PlayerAssets::SHEET_PATH);
+	commands.insert_resource(PlayerAssets {
+		sprite: asset_server.load("sprites/space_invader_player.png"),
+		atlas: atlases.add(TextureAtlas::from_grid(
+			player_sheet.clone(),
+			PlayerAssets::FRAME_SIZE,
+			PlayerAssets::COLUMNS,
+			1,
+			None,
+			None,
+		)),
+		sheet: player_sheet,
+	});
+
+	commands.insert_resource(BulletAssets {
+		sprite: asset_server.load("sprites/bullet_player.png"),
+		mesh: meshes.add(shape::Quad::new(BULLET_SIZE).into()).into(),
+		material: materials.add(ColorMaterial::from(Faction::Player.bullet_colour(&theme, &settings))),
+	});
+
+	commands.spawn((
+		Camera2dBundle::default(),
+		// Turned off while the CRT camera is drawing the UI instead
+		UiCameraConfig { show_ui: true },
+		MainCamera,
+	));
+
+	// The debug overlay lives for the whole app (not just a run), so players can
+	// report performance from any screen. Hidden until F3 is pressed.
+	let mut debug_overlay = TextBundle::from_section(
+		"",
+		TextStyle {
+			font: asset_server.load("fonts/dejavu/DejaVuSansMono.ttf"),
+			font_size: DEBUG_OVERLAY_FONT_SIZE,
+			color: DEBUG_OVERLAY_COLOUR,
+		},
+	)
+	.with_style(Style {
+		position_type: PositionType::Absolute,
+		position: UiRect {
+			top: Val::Px(5.0),
+			right: Val::Px(5.0),
+			..default()
+		},
+		..default()
+	});
+	debug_overlay.visibility = Visibility::Hidden;
+	debug_overlay.z_index = ZIndex::Global(i32::MAX);
+
+	commands.spawn((debug_overlay, DebugOverlay));
+}
+
+fn crt_setup(
+	mut commands: Commands,
+	mut images: ResMut<Assets<Image>>,
+	mut meshes: ResMut<Assets<Mesh>>,
+	mut materials: ResMut<Assets<CrtMaterial>>,
+	settings: Res<Settings>,
+) {
+	let size = Extent3d {
+		width: WIDTH as u32,
+		height: HEIGHT as u32,
+		..default()
+	};
+
+	let mut image = Image {
+		texture_descriptor: TextureDescriptor {
+			label: None,
+			size,
+			dimension: TextureDimension::D2,
+			format: TextureFormat::Bgra8UnormSrgb,
+			mip_level_count: 1,
+			sample_count: 1,
+			usage: TextureUsages::TEXTURE_BINDING
+				| TextureUsages::COPY_DST
+				| TextureUsages::RENDER_ATTACHMENT,
+			view_formats: &[],
+		},
+		..default()
+	};
+	image.resize(size);
+
+	let image = images.add(image);
+	let material = materials.add(CrtMaterial {
+		intensity: settings.crt_intensity,
+		source: image.clone(),
+	});
+
+	let layer = RenderLayers::layer(CRT_RENDER_LAYER);
+
+	// A screen-sized quad showing the game through the CRT shader
+	commands.spawn((
+		MaterialMesh2dBundle {
+			mesh: meshes.add(shape::Quad::new(Vec2::new(WIDTH, HEIGHT)).into()).into(),
+			material: material.clone(),
+			..default()
+		},
+		layer,
+	));
+
+	// Drawn after the main camera, straight onto the window. The UI is drawn by
+	// this camera so it stays sharp and readable.
+	commands.spawn((
+		Camera2dBundle {
+			camera: Camera {
+				order: 1,
+				is_active: false,
+				..default()
+			},
+			..default()
+		},
+		layer,
+		CrtCamera,
+	));
+
+	commands.insert_resource(CrtAssets { image, material });
+}
+
+// Resize and re-centre the window when the size or monitor setting changes.
+// The scale factor is overridden to match, so the logical size (and so the
+// playfield, UI and cameras) stays at WIDTH x HEIGHT whatever the window size.
+fn apply_window_settings(
+	settings: Res<Settings>,
+	// Not there at all in a headless app
+	winit_windows: Option<NonSend<WinitWindows>>,
+	mut monitors: ResMut<Monitors>,
+	mut applied: Local<Option<(WindowSize, usize)>>,
+	mut window_query: Query<(Entity, &mut Window), With<PrimaryWindow>>,
+) {
+	if *applied == Some((settings.window_size, settings.monitor)) {
+		return;
+	}
+
+	let Ok((entity, mut window)) = window_query.get_single_mut() else {
+		return;
+	};
+
+	// The winit window doesn't exist for the first frame or so
+	let Some(winit_window) = winit_windows.as_ref().and_then(|windows| windows.get_window(entity)) else {
+		return;
+	};
+
+	monitors.sizes = winit_window
+		.available_monitors()
+		.map(|monitor| UVec2::new(monitor.size().width, monitor.size().height))
+		.collect();
+	monitors.positions = winit_window
+		.available_monitors()
+		.map(|monitor| IVec2::new(monitor.position().x, monitor.position().y))
+		.collect();
+
+	// Only put the window back where it was at startup. Changing the size or
+	// monitor in the settings menu should move it.
+	let starting = applied.is_none();
+	*applied = Some((settings.window_size, settings.monitor));
+
+	// A monitor may have been unplugged since the settings were saved
+	let monitor = settings.monitor.min(monitors.sizes.len().saturating_sub(1));
+
+	let mut window_size = settings.window_size;
+	if let Some(&monitor_size) = monitors.sizes.get(monitor) {
+		if !window_size.fits(monitor_size) {
+			let fitting = WindowSize::ALL
+				.into_iter()
+				.take_while(|size| size.fits(monitor_size))
+				.last()
+				.unwrap_or(WindowSize::Hd);
+
+			warn!(
+				"A {} window doesn't fit on monitor {} ({}x{}), using {} instead",
+				window_size.name(),
+				monitor + 1,
+				monitor_size.x,
+				monitor_size.y,
+				fitting.name(),
+			);
+			window_size = fitting;
+		}
+	}
+
+	let size = window_size.size();
+	window.resolution.set_scale_factor_override(Some(size.x as f64 / WIDTH as f64));
+	window.resolution.set_physical_resolution(size.x, size.y);
+
+	let restored = settings
+		.window
+		.filter(|_| starting)
+		.and_then(|state| monitors.clamp_window(IVec2::new(state.x, state.y), size));
+
+	window.position = match restored {
+		Some(position) => WindowPosition::At(position),
+		None => WindowPosition::Centered(MonitorSelection::Index(monitor)),
+	};
+}
+
+// "Bevy Invaders — Wave 4 — 2,350" while a run's going, the plain title
+// everywhere else. Does nothing without a window.
+fn update_window_title(
+	time: Res<Time>,
+	app_state: Res<State<AppState>>,
+	strings: Res<Strings>,
+	wave: Res<Wave>,
+	scoreboard: Option<Res<Scoreboard>>,
+	mut next_refresh: Local<f32>,
+	mut window_query: Query<&mut Window, With<PrimaryWindow>>,
+) {
+	let Ok(mut window) = window_query.get_single_mut() else {
+		return;
+	};
+
+	let title = match (&app_state.0, scoreboard) {
+		(AppState::GameRunning, Some(scoreboard)) => {
+			// Real time, so it still catches up while paused
+			let now = time.raw_elapsed_seconds();
+			if now < *next_refresh {
+				return;
+			}
+			*next_refresh = now + WINDOW_TITLE_REFRESH_SECS;
+
+			let wave = strings.format("hud.wave", &[&wave.number.to_string()]);
+			let title = format!("{WINDOW_TITLE} \u{2014} {wave} \u{2014} {}", format_thousands(scoreboard.score));
+			title.chars().take(WINDOW_TITLE_MAX_CHARS).collect()
+		},
+		_ => {
+			*next_refresh = 0.0;
+			WINDOW_TITLE.to_string()
+		},
+	};
+
+	// Only touch the window when it's actually different, winit does the rest
+	if window.title != title {
+		window.title = title;
+	}
+}
+
+// Save where the window's been moved to, once it stops moving
+fn remember_window_position(
+	time: Res<Time>,
+	mut moved_events: EventReader<WindowMoved>,
+	mut settings: ResMut<Settings>,
+	mut pending: Local<Option<(IVec2, Timer)>>,
+) {
+	if let Some(event) = moved_events.iter().last() {
+		*pending = Some((event.position, Timer::from_seconds(WINDOW_SAVE_DELAY_SECS, TimerMode::Once)));
+	}
+
+	let Some((position, timer)) = pending.as_mut() else {
+		return;
+	};
+	if !timer.tick(time.delta()).finished() {
+		return;
+	}
+
+	let state = WindowState { x: position.x, y: position.y };
+	*pending = None;
+
+	// Don't rewrite the settings file for a window that ended up where it started
+	if settings.window != Some(state) {
+		settings.window = Some(state);
+	}
+}
+
+fn apply_crt_settings(
+	settings: Res<Settings>,
+	crt_assets: Res<CrtAssets>,
+	mut materials: ResMut<Assets<CrtMaterial>>,
+	mut main_camera_query: Query<(&mut Camera, &mut UiCameraConfig), (With<MainCamera>, Without<CrtCamera>)>,
+	mut crt_camera_query: Query<&mut Camera, (With<CrtCamera>, Without<MainCamera>)>,
+) {
+	if !settings.is_changed() {
+		return;
+	}
+
+	// The effect isn't supported on the web build (WebGL2), so it just stays off
+	let enabled = settings.crt && !cfg!(target_arch = "wasm32");
+	if settings.crt && !enabled {
+		info!("The CRT effect isn't available on the web build, leaving it off");
+	}
+
+	if let Some(material) = materials.get_mut(&crt_assets.material) {
+		material.intensity = settings.crt_intensity;
+	}
+
+	for (mut camera, mut ui_config) in &mut main_camera_query {
+		camera.target = if enabled {
+			RenderTarget::Image(crt_assets.image.clone())
+		} else {
+			RenderTarget::default()
+		};
+		ui_config.show_ui = !enabled;
+	}
+
+	for mut camera in &mut crt_camera_query {
+		camera.is_active = enabled;
+	}
+}
+
+fn menu_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	strings: Res<Strings>,
+	config: Res<GameConfig>,
+	enemy_catalog: Res<EnemyCatalog>,
+) {
+	let font = strings.font(&asset_server);
+
+	spawn_menu_demo(&mut commands, &theme, &config, &enemy_catalog);
+
+	commands.spawn(
+		(
+			NodeBundle {
+				style: Style {
+					size: Size::width(Val::Percent(100.0)),
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					justify_content: JustifyContent::Center,
+					..default()
+				},
+				..default()
+			},
+			Menu,
+		)
+	)
+	.with_children(|parent| {
+		spawn_button(parent, strings.get("menu.start"), font.clone(), &theme, MenuButtonAction::Play);
+		spawn_button(parent, strings.get("menu.challenge"), font.clone(), &theme, MenuButtonAction::Challenge);
+		spawn_button(parent, strings.get("menu.mutators"), font.clone(), &theme, MenuButtonAction::Mutators);
+		spawn_button(parent, strings.get("menu.settings"), font.clone(), &theme, MenuButtonAction::Settings);
+	});
+}
+
+fn spawn_button(
+	parent: &mut ChildBuilder,
+	label: &str,
+	font: Handle<Font>,
+	theme: &Theme,
+	action: impl Component,
+) {
+	parent
+		.spawn((
+			ButtonBundle {
+				style: Style {
+					size: Size::new(Val::Px(300.0), Val::Px(65.0)),
+					margin: UiRect::all(Val::Px(10.0)),
+					justify_content: JustifyContent::Center,
+					align_items: AlignItems::Center,
+					..default()
+				},
+				background_color: theme.button.into(),
+				..default()
+			},
+			action,
+		))
+		.with_children(|parent| {
+			parent.spawn((
+				TextBundle::from_section(
+					label,
+					TextStyle {
+						font,
+						font_size: BUTTON_FONT_SIZE,
+						color: theme.button_text,
+					},
+				),
+				ButtonLabel,
+			));
+		});
+}
+
+// Hover colours and sounds for every button on every screen, so the screens
+// themselves only have to deal with what their buttons do
+fn ui_feedback(
+	theme: Res<Theme>,
+	mut sound_events: EventWriter<PlaySound>,
+	mut hovered: Local<Vec<Entity>>,
+	mut interaction_query: Query<(Entity, &Interaction, &mut BackgroundColor), (Changed<Interaction>, With<Button>)>,
+	button_query: Query<(), With<Button>>,
+) {
+	// Forget buttons that went away with their screen while hovered
+	hovered.retain(|&entity| button_query.contains(entity));
+
+	for (entity, interaction, mut colour) in &mut interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				*colour = theme.button_hovered.into();
+				sound_events.send(PlaySound::new(SoundId::UiClick));
+			},
+			Interaction::Hovered => {
+				*colour = theme.button_hovered.into();
+
+				// Letting go of a click goes back to Hovered, which isn't a new hover
+				if !hovered.contains(&entity) {
+					hovered.push(entity);
+					sound_events.send(PlaySound::new(SoundId::UiHover));
+				}
+			},
+			Interaction::None => {
+				*colour = theme.button.into();
+				hovered.retain(|&hovered_entity| hovered_entity != entity);
+			},
+		}
+	}
+}
+
+fn menu(
+	interaction_query: Query<
+		(&Interaction, &MenuButtonAction),
+		(Changed<Interaction>, With<Button>),
+	>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	for (interaction, action) in &interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				match action {
+					MenuButtonAction::Play => app_state.set(AppState::GameRunning),
+					MenuButtonAction::Challenge => app_state.set(AppState::Challenge),
+					MenuButtonAction::Mutators => app_state.set(AppState::Mutators),
+					MenuButtonAction::Settings => app_state.set(AppState::Settings),
+				}
+			},
+			// Colours and sounds are handled by ui_feedback
+			Interaction::Hovered | Interaction::None => {},
+		}
+	}
+}
+
+// Lay out a faint formation behind the menu. game_setup replaces the
+// orientation and formation resources when a run starts.
+fn spawn_menu_demo(commands: &mut Commands, theme: &Theme, config: &GameConfig, enemy_catalog: &EnemyCatalog) {
+	commands.insert_resource(PlayfieldOrientation::default());
+	commands.insert_resource(menu_demo_formation(config));
+
+	let size = enemy_catalog.get(EnemyKind::Invader).size();
+	let grid_width = (ENEMY_COLUMNS - 1) as f32 * ENEMY_SPACING.x;
+	let mut colour = theme.enemy;
+	colour.set_a(MENU_DEMO_ALPHA);
+
+	for row in 0..ENEMY_ROWS {
+		for column in 0..ENEMY_COLUMNS {
+			let x = -(grid_width / 2.0) + column as f32 * ENEMY_SPACING.x;
+			let y = ENEMY_GRID_TOP - row as f32 * ENEMY_SPACING.y;
+
+			// Behind the menu, though the UI is drawn over it anyway
+			let mut sprite = enemy_sprite(theme, size, Vec3::new(x, y, -1.0));
+			sprite.sprite.color = colour;
+
+			commands.spawn((
+				sprite,
+				MenuDemo,
+				Menu,
+				Collider { half_extents: size / 2.0 },
+			));
+		}
+	}
+}
+
+fn menu_demo_formation(config: &GameConfig) -> FormationParams {
+	let mut formation = FormationParams::for_wave(1, config);
+	formation.speed *= MENU_DEMO_SPEED;
+	formation
+}
+
+// Once the demo formation has marched down far enough, put it back at the top
+fn loop_menu_demo(
+	config: Res<GameConfig>,
+	mut formation: ResMut<FormationParams>,
+	mut query: Query<&mut Transform, With<MenuDemo>>,
+) {
+	let lowest = query.iter().map(|transform| transform.translation.y).fold(f32::INFINITY, f32::min);
+	if lowest > MENU_DEMO_LOWEST {
+		return;
+	}
+
+	let highest = query.iter().map(|transform| transform.translation.y).fold(f32::NEG_INFINITY, f32::max);
+	for mut transform in &mut query {
+		transform.translation.y += ENEMY_GRID_TOP - highest;
+	}
+
+	*formation = menu_demo_formation(&config);
+}
+
+fn menu_cleanup(
+	mut commands: Commands,
+	query: Query<Entity, With<Menu>>,
+) {
+	for entity in query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+fn settings_menu_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	strings: Res<Strings>,
+) {
+	let font = strings.font(&asset_server);
+
+	commands.spawn(
+		(
+			NodeBundle {
+				style: Style {
+					size: Size::width(Val::Percent(100.0)),
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					justify_content: JustifyContent::Center,
+					..default()
+				},
+				..default()
+			},
+			SettingsMenu,
+		)
+	)
+	.with_children(|parent| {
+		// Two columns of buttons, there are too many options for one
+		parent.spawn(NodeBundle {
+			style: Style {
+				size: Size::width(Val::Px(680.0)),
+				flex_wrap: FlexWrap::Wrap,
+				justify_content: JustifyContent::Center,
+				..default()
+			},
+			..default()
+		})
+		.with_children(|parent| {
+			for action in [
+				SettingsButtonAction::Theme,
+				SettingsButtonAction::Hitstop,
+				SettingsButtonAction::Crt,
+				SettingsButtonAction::CrtIntensity,
+				SettingsButtonAction::Bloom,
+				SettingsButtonAction::BloomIntensity,
+				SettingsButtonAction::EventLog,
+				SettingsButtonAction::Finisher,
+				SettingsButtonAction::FlyIn,
+				SettingsButtonAction::ReducedMotion,
+				SettingsButtonAction::WindowSize,
+				SettingsButtonAction::Monitor,
+				SettingsButtonAction::Language,
+				SettingsButtonAction::Assist,
+				SettingsButtonAction::Announcer,
+				SettingsButtonAction::MusicDucking,
+				SettingsButtonAction::Back,
+			] {
+				spawn_button(parent, &action.label(&settings, &strings), font.clone(), &theme, action);
+			}
+		});
+	});
+}
+
+fn settings_menu(
+	interaction_query: Query<
+		(&Interaction, &SettingsButtonAction),
+		(Changed<Interaction>, With<Button>),
+	>,
+	keyboard_input: Res<Input<KeyCode>>,
+	monitors: Res<Monitors>,
+	mut settings: ResMut<Settings>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	if keyboard_input.just_pressed(KeyCode::Escape) {
+		app_state.set(AppState::Menu);
+	}
+
+	for (interaction, action) in &interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				match action {
+					SettingsButtonAction::Theme => settings.theme = settings.theme.next(),
+					SettingsButtonAction::Hitstop => settings.hitstop = !settings.hitstop,
+					SettingsButtonAction::Crt => settings.crt = !settings.crt,
+					SettingsButtonAction::CrtIntensity => {
+						settings.crt_intensity = step_fraction(settings.crt_intensity);
+					},
+					SettingsButtonAction::Bloom => settings.bloom = !settings.bloom,
+					SettingsButtonAction::EventLog => settings.event_log = !settings.event_log,
+					SettingsButtonAction::Finisher => settings.finisher = !settings.finisher,
+					SettingsButtonAction::FlyIn => settings.fly_in = !settings.fly_in,
+					SettingsButtonAction::ReducedMotion => settings.reduced_motion = !settings.reduced_motion,
+					SettingsButtonAction::Assist => settings.assist = !settings.assist,
+					SettingsButtonAction::Announcer => settings.announcer = !settings.announcer,
+					SettingsButtonAction::MusicDucking => settings.music_ducking = !settings.music_ducking,
+					SettingsButtonAction::WindowSize => settings.window_size = settings.window_size.next(),
+					SettingsButtonAction::Monitor => {
+						settings.monitor = (settings.monitor + 1) % monitors.sizes.len().max(1);
+					},
+					SettingsButtonAction::Language => {
+						settings.language = settings.language.next();
+						// Re-enter the screen to rebuild it in the new language (and font)
+						app_state.set(AppState::Settings);
+					},
+					SettingsButtonAction::BloomIntensity => {
+						settings.bloom_intensity = step_fraction(settings.bloom_intensity);
+					},
+					SettingsButtonAction::Back => app_state.set(AppState::Menu),
+				}
+			},
+			// Colours and sounds are handled by ui_feedback
+			Interaction::Hovered | Interaction::None => {},
+		}
+	}
+}
+
+fn update_settings_labels(
+	settings: Res<Settings>,
+	strings: Res<Strings>,
+	button_query: Query<(&SettingsButtonAction, &Children)>,
+	mut text_query: Query<&mut Text, With<ButtonLabel>>,
+) {
+	if !settings.is_changed() && !strings.is_changed() {
+		return;
+	}
+
+	for (action, children) in &button_query {
+		for &child in children.iter() {
+			if let Ok(mut text) = text_query.get_mut(child) {
+				text.sections[0].value = action.label(&settings, &strings);
+			}
+		}
+	}
+}
+
+fn reload_strings(settings: Res<Settings>, mut strings: ResMut<Strings>) {
+	if settings.language != strings.language {
+		*strings = Strings::load(settings.language);
+	}
+}
+
+fn settings_menu_cleanup(
+	mut commands: Commands,
+	query: Query<Entity, With<SettingsMenu>>,
+) {
+	for entity in query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+fn mutators_menu_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	mutators: Res<Mutators>,
+	strings: Res<Strings>,
+) {
+	let font = strings.font(&asset_server);
+
+	commands.spawn(
+		(
+			NodeBundle {
+				style: Style {
+					size: Size::width(Val::Percent(100.0)),
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					justify_content: JustifyContent::Center,
+					..default()
+				},
+				..default()
+			},
+			MutatorsMenu,
+		)
+	)
+	.with_children(|parent| {
+		for action in [
+			MutatorButtonAction::BulletGravity,
+			MutatorButtonAction::TinyPlayer,
+			MutatorButtonAction::Ricochet,
+			MutatorButtonAction::Mirror,
+			MutatorButtonAction::Wrap,
+			MutatorButtonAction::Back,
+		] {
+			spawn_button(parent, &action.label(&mutators, &strings), font.clone(), &theme, action);
+		}
+	});
+}
+
+fn mutators_menu(
+	interaction_query: Query<
+		(&Interaction, &MutatorButtonAction),
+		(Changed<Interaction>, With<Button>),
+	>,
+	keyboard_input: Res<Input<KeyCode>>,
+	mut mutators: ResMut<Mutators>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	if keyboard_input.just_pressed(KeyCode::Escape) {
+		app_state.set(AppState::Menu);
+	}
+
+	for (interaction, action) in &interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				match action {
+					MutatorButtonAction::BulletGravity => mutators.bullet_gravity = !mutators.bullet_gravity,
+					MutatorButtonAction::TinyPlayer => mutators.tiny_player = !mutators.tiny_player,
+					MutatorButtonAction::Ricochet => mutators.ricochet = !mutators.ricochet,
+					MutatorButtonAction::Mirror => mutators.mirror = !mutators.mirror,
+					MutatorButtonAction::Wrap => mutators.wrap = !mutators.wrap,
+					MutatorButtonAction::Back => app_state.set(AppState::Menu),
+				}
+			},
+			// Colours and sounds are handled by ui_feedback
+			Interaction::Hovered | Interaction::None => {},
+		}
+	}
+}
+
+fn update_mutator_labels(
+	mutators: Res<Mutators>,
+	strings: Res<Strings>,
+	button_query: Query<(&MutatorButtonAction, &Children)>,
+	mut text_query: Query<&mut Text, With<ButtonLabel>>,
+) {
+	if !mutators.is_changed() {
+		return;
+	}
+
+	for (action, children) in &button_query {
+		for &child in children.iter() {
+			if let Ok(mut text) = text_query.get_mut(child) {
+				text.sections[0].value = action.label(&mutators, &strings);
+			}
+		}
+	}
+}
+
+fn mutators_menu_cleanup(
+	mut commands: Commands,
+	query: Query<Entity, With<MutatorsMenu>>,
+) {
+	for entity in query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+// Back at the main menu the player's own options apply again
+fn clear_challenge(
+	mut active_challenge: ResMut<ActiveChallenge>,
+	mut saved_difficulty: ResMut<SavedDifficulty>,
+	mut config: ResMut<GameConfig>,
+) {
+	active_challenge.0 = None;
+	if let Some(difficulty) = saved_difficulty.0.take() {
+		config.difficulty = difficulty;
+	}
+}
+
+fn challenge_menu_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	strings: Res<Strings>,
+	mut input: ResMut<ChallengeInput>,
+) {
+	let font = strings.font(&asset_server);
+	*input = ChallengeInput::default();
+
+	commands.spawn(
+		(
+			NodeBundle {
+				style: Style {
+					size: Size::width(Val::Percent(100.0)),
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					justify_content: JustifyContent::Center,
+					..default()
+				},
+				..default()
+			},
+			ChallengeMenu,
+		)
+	)
+	.with_children(|parent| {
+		parent.spawn(TextBundle::from_section(
+			strings.get("challenge.title"),
+			TextStyle {
+				font: font.clone(),
+				font_size: BUTTON_FONT_SIZE,
+				color: theme.scoreboard,
+			},
+		));
+		parent.spawn((
+			TextBundle::from_section(
+				challenge_input_label(&input.text),
+				TextStyle {
+					font: font.clone(),
+					font_size: CHALLENGE_INPUT_FONT_SIZE,
+					color: theme.scoreboard,
+				},
+			)
+			.with_style(Style {
+				margin: UiRect::all(Val::Px(10.0)),
+				..default()
+			}),
+			ChallengeInputText,
+		));
+		parent.spawn((
+			TextBundle::from_section(
+				"",
+				TextStyle {
+					font: font.clone(),
+					font_size: BUTTON_FONT_SIZE,
+					color: CHALLENGE_ERROR_COLOUR,
+				},
+			),
+			ChallengeErrorText,
+		));
+		spawn_button(parent, strings.get("challenge.play"), font.clone(), &theme, ChallengeButtonAction::Play);
+		spawn_button(parent, strings.get("common.back"), font, &theme, ChallengeButtonAction::Back);
+	});
+}
+
+// "AB3" -> "AB3_________"
+fn challenge_input_label(text: &str) -> String {
+	format!("{text:_<width$}", width = CHALLENGE_CODE_LEN)
+}
+
+fn challenge_input(
+	mut keyboard_input: ResMut<Input<KeyCode>>,
+	mut characters: EventReader<ReceivedCharacter>,
+	mut input: ResMut<ChallengeInput>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	for character in characters.iter() {
+		// Only what can be in a code, so typing lowercase works too
+		let character = character.char.to_ascii_uppercase();
+		if input.text.len() < CHALLENGE_CODE_LEN && CHALLENGE_ALPHABET.contains(&(character as u8)) {
+			input.text.push(character);
+			input.error = None;
+		}
+	}
+
+	if keyboard_input.just_pressed(KeyCode::Back) {
+		input.text.pop();
+		input.error = None;
+	}
+
+	if keyboard_input.just_pressed(KeyCode::Return) {
+		input.submitted = true;
+	}
+
+	if keyboard_input.just_pressed(KeyCode::Escape) {
+		app_state.set(AppState::Menu);
+	}
+
+	// Swallow the keyboard while typing, so a code with a W in it doesn't carry
+	// on into the run
+	keyboard_input.reset_all();
+}
+
+fn challenge_menu(
+	interaction_query: Query<
+		(&Interaction, &ChallengeButtonAction),
+		(Changed<Interaction>, With<Button>),
+	>,
+	mut input: ResMut<ChallengeInput>,
+	mut active_challenge: ResMut<ActiveChallenge>,
+	mut mutators: ResMut<Mutators>,
+	mut assist: ResMut<AssistSettings>,
+	mut config: ResMut<GameConfig>,
+	mut saved_difficulty: ResMut<SavedDifficulty>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	let mut submitted = std::mem::take(&mut input.submitted);
+
+	for (interaction, action) in &interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				match action {
+					ChallengeButtonAction::Play => submitted = true,
+					ChallengeButtonAction::Back => app_state.set(AppState::Menu),
+				}
+			},
+			// Colours and sounds are handled by ui_feedback
+			Interaction::Hovered | Interaction::None => {},
+		}
+	}
+
+	if !submitted {
+		return;
+	}
+
+	match ChallengeCode::decode(&input.text) {
+		Ok(code) => {
+			// Set here rather than left to sync_assist, which wouldn't get to it
+			// before game_setup
+			*mutators = code.mutators;
+			assist.enabled = code.assist;
+			saved_difficulty.0.get_or_insert(config.difficulty);
+			config.difficulty = code.difficulty;
+			active_challenge.0 = Some(code);
+			app_state.set(AppState::GameRunning);
+		},
+		Err(error) => input.error = Some(error),
+	}
+}
+
+fn update_challenge_text(
+	input: Res<ChallengeInput>,
+	strings: Res<Strings>,
+	mut input_query: Query<&mut Text, (With<ChallengeInputText>, Without<ChallengeErrorText>)>,
+	mut error_query: Query<&mut Text, With<ChallengeErrorText>>,
+) {
+	if !input.is_changed() {
+		return;
+	}
+
+	for mut text in &mut input_query {
+		text.sections[0].value = challenge_input_label(&input.text);
+	}
+	for mut text in &mut error_query {
+		text.sections[0].value = input.error.map_or("", |error| strings.get(error.key())).to_string();
+	}
+}
+
+fn challenge_menu_cleanup(
+	mut commands: Commands,
+	query: Query<Entity, With<ChallengeMenu>>,
+) {
+	for entity in query.iter() {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+// Nothing is shown after a run yet, so Esc or Enter just heads back to the menu
+fn game_over_input(
+	keyboard_input: Res<Input<KeyCode>>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	if keyboard_input.any_just_pressed([KeyCode::Escape, KeyCode::Return]) {
+		app_state.set(AppState::Menu);
+	}
+}
+
+// Closing the window mid-run asks first, anywhere else it just quits. In a
+// browser the tab closes without asking the game, so this never sees it.
+// Esc counts as closing on the main menu and during a run. Other screens use it
+// for going back.
+fn handle_close_requests(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	keyboard_input: Res<Input<KeyCode>>,
+	theme: Res<Theme>,
+	strings: Res<Strings>,
+	state: Res<State<AppState>>,
+	run_code: Res<RunCode>,
+	mut time: ResMut<Time>,
+	mut close_events: EventReader<WindowCloseRequested>,
+	mut exit_events: EventWriter<AppExit>,
+	dialog_query: Query<(), With<QuitDialog>>,
+) {
+	let escape = keyboard_input.just_pressed(KeyCode::Escape)
+		&& matches!(state.0, AppState::Menu | AppState::GameRunning);
+
+	if close_events.iter().last().is_none() && !escape {
+		return;
+	}
+
+	if state.0 != AppState::GameRunning {
+		exit_events.send(AppExit);
+		return;
+	}
+
+	// Already asking
+	if !dialog_query.is_empty() {
+		return;
+	}
+
+	// Nothing moves while they decide
+	time.pause();
+
+	let font = strings.font(&asset_server);
+	commands
+		.spawn((
+			NodeBundle {
+				style: Style {
+					position_type: PositionType::Absolute,
+					size: Size::all(Val::Percent(100.0)),
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					justify_content: JustifyContent::Center,
+					..default()
+				},
+				background_color: QUIT_DIALOG_BG_COLOUR.into(),
+				z_index: ZIndex::Global(1),
+				..default()
+			},
+			QuitDialog,
+		))
+		.with_children(|parent| {
+			parent.spawn(TextBundle::from_section(
+				strings.get("quit.title"),
+				TextStyle {
+					font: font.clone(),
+					font_size: BUTTON_FONT_SIZE,
+					color: theme.scoreboard,
+				},
+			));
+			// The run's about to be gone, so this is the last chance to note it down
+			if let Some(code) = run_code.0 {
+				parent.spawn(TextBundle::from_section(
+					strings.format("quit.challenge_code", &[&code.encode()]),
+					TextStyle {
+						font: font.clone(),
+						font_size: BUTTON_FONT_SIZE,
+						color: theme.scoreboard,
+					},
+				));
+			}
+			spawn_button(parent, strings.get("quit.confirm"), font.clone(), &theme, QuitDialogAction::Quit);
+			spawn_button(parent, strings.get("common.cancel"), font, &theme, QuitDialogAction::Cancel);
+		});
+}
+
+fn quit_dialog(
+	mut commands: Commands,
+	mut keyboard_input: ResMut<Input<KeyCode>>,
+	mut time: ResMut<Time>,
+	mut exit_events: EventWriter<AppExit>,
+	interaction_query: Query<(&Interaction, &QuitDialogAction), (Changed<Interaction>, With<Button>)>,
+	dialog_query: Query<Entity, With<QuitDialog>>,
+) {
+	let Ok(dialog) = dialog_query.get_single() else {
+		return;
+	};
+
+	let mut cancelled = keyboard_input.just_pressed(KeyCode::Escape);
+	// Keep the keys away from the paused game, or Space still fires
+	keyboard_input.reset_all();
+
+	for (interaction, action) in &interaction_query {
+		match *interaction {
+			Interaction::Clicked => {
+				match action {
+					QuitDialogAction::Quit => exit_events.send(AppExit),
+					QuitDialogAction::Cancel => cancelled = true,
+				}
+			},
+			// Colours and sounds are handled by ui_feedback
+			Interaction::Hovered | Interaction::None => {},
+		}
+	}
+
+	if cancelled {
+		commands.entity(dialog).despawn_recursive();
+		time.unpause();
+	}
+}
+
+fn save_settings(settings: Res<Settings>) {
+	// Don't bother writing out the settings we've just loaded
+	if settings.is_changed() && !settings.is_added() {
+		settings.save();
+	}
+}
+
+fn sync_assist(
+	settings: Res<Settings>,
+	active_challenge: Res<ActiveChallenge>,
+	mut assist: ResMut<AssistSettings>,
+) {
+	let enabled = active_challenge.0.map_or(settings.assist, |challenge| challenge.assist);
+
+	if assist.enabled != enabled {
+		assist.enabled = enabled;
+	}
+}
+
+fn sync_theme(
+	settings: Res<Settings>,
+	mut theme: ResMut<Theme>,
+) {
+	let selected = settings.theme.theme();
+
+	if *theme != selected {
+		*theme = selected;
+	}
+}
+
+// Retint everything already on screen when the theme changes
+fn apply_theme(
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	bullet_assets: Res<BulletAssets>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+	mut clear_colour: ResMut<ClearColor>,
+	mut button_query: Query<(&Interaction, &mut BackgroundColor), With<Button>>,
+	mut label_query: Query<&mut Text, (With<ButtonLabel>, Without<HudText>)>,
+	mut hud_query: Query<&mut Text, (With<HudText>, Without<ButtonLabel>)>,
+	mut enemy_query: Query<&mut Sprite, (With<Enemy>, Without<Bullet>)>,
+	mut bullet_query: Query<(&mut Sprite, &Faction), (With<Bullet>, Without<Enemy>)>,
+) {
+	if !theme.is_changed() {
+		return;
+	}
+
+	clear_colour.0 = theme.background;
+
+	if let Some(material) = materials.get_mut(&bullet_assets.material) {
+		material.color = Faction::Player.bullet_colour(&theme, &settings);
+	}
+	for (mut sprite, faction) in &mut bullet_query {
+		sprite.color = faction.bullet_colour(&theme, &settings);
+	}
+
+	for (interaction, mut colour) in &mut button_query {
+		*colour = match *interaction {
+			Interaction::None => theme.button.into(),
+			_ => theme.button_hovered.into(),
+		};
+	}
+
+	for mut text in &mut label_query {
+		for section in &mut text.sections {
+			section.style.color = theme.button_text;
+		}
+	}
+
+	for mut text in &mut hud_query {
+		for section in &mut text.sections {
+			section.style.color = theme.scoreboard;
+		}
+	}
+
+	for mut sprite in &mut enemy_query {
+		sprite.color = theme.enemy;
+	}
+}
+
+fn apply_bloom_settings(
+	mut commands: Commands,
+	settings: Res<Settings>,
+	theme: Res<Theme>,
+	bullet_assets: Res<BulletAssets>,
+	mut materials: ResMut<Assets<ColorMaterial>>,
+	mut camera_query: Query<(Entity, &mut Camera), With<MainCamera>>,
+	mut bullet_query: Query<(&mut Sprite, &Faction), With<Bullet>>,
+) {
+	if !settings.is_changed() {
+		return;
+	}
+
+	if settings.bloom && !bloom_available() {
+		warn!("HDR isn't available on this platform, so bloom has been turned off");
+	}
+
+	let enabled = bloom_enabled(&settings);
+
+	// Bloom only touches the main camera's pass. The UI is drawn afterwards, so
+	// the scoreboard and menus never glow.
+	for (entity, mut camera) in &mut camera_query {
+		camera.hdr = enabled;
+
+		if enabled {
+			commands.entity(entity).insert(BloomSettings {
+				intensity: settings.bloom_intensity,
+				..default()
+			});
+		} else {
+			commands.entity(entity).remove::<BloomSettings>();
+		}
+	}
+
+	// Bullets only get their over-bright colour while bloom is on, otherwise it
+	// would just be clamped and shift the hue
+	if let Some(material) = materials.get_mut(&bullet_assets.material) {
+		material.color = Faction::Player.bullet_colour(&theme, &settings);
+	}
+	for (mut sprite, faction) in &mut bullet_query {
+		sprite.color = faction.bullet_colour(&theme, &settings);
+	}
+}
+
+// HDR render targets aren't available on the web build (WebGL2)
+fn bloom_available() -> bool {
+	!cfg!(target_arch = "wasm32")
+}
+
+fn bloom_enabled(settings: &Settings) -> bool {
+	settings.bloom && bloom_available()
+}
+
+fn glow_colour(colour: Color, settings: &Settings) -> Color {
+	if !bloom_enabled(settings) {
+		return colour;
+	}
+
+	let [r, g, b, a] = colour.as_rgba_f32();
+	Color::rgba(r * BULLET_GLOW, g * BULLET_GLOW, b * BULLET_GLOW, a)
+}
+
+// Steps a 0-1 setting up by SETTINGS_FRACTION_STEP, wrapping back round to the
+// lowest step after 1. Snaps to the step so float error can't build up.
+fn step_fraction(value: f32) -> f32 {
+	let steps = (value / SETTINGS_FRACTION_STEP).round() + 1.0;
+	let next = steps * SETTINGS_FRACTION_STEP;
+
+	if next > 1.0 + SETTINGS_FRACTION_STEP / 2.0 {
+		SETTINGS_FRACTION_STEP
+	} else {
+		next
+	}
+}
+
+// Starting a run twice (after a game over, say) mustn't leave the last run's
+// player, HUD or score behind, or the get_single queries find two of everything.
+// Resources that are always there are reset in place. The rest are inserted
+// with commands, which are applied before game_setup runs.
+fn reset_run(
+	mut commands: Commands,
+	mut displayed_score: ResMut<DisplayedScore>,
+	mut tally: ResMut<WaveTally>,
+	mut hitstop: ResMut<Hitstop>,
+	mut tainted: ResMut<TaintedRun>,
+	mut modifiers: ResMut<FormationModifiers>,
+	mut run_code: ResMut<RunCode>,
+	mut rng: ResMut<GameRng>,
+	active_challenge: Res<ActiveChallenge>,
+	mutators: Res<Mutators>,
+	assist: Res<AssistSettings>,
+	config: Res<GameConfig>,
+	query: Query<Entity, With<GameEntity>>,
+) {
+	let mut leftover = 0;
+	for entity in &query {
+		commands.entity(entity).despawn_recursive();
+		leftover += 1;
+	}
+	if leftover > 0 {
+		info!("Cleared {leftover} entities left over from the last run");
+	}
+
+	// Inserted rather than reset, in case the inspector removed it last run
+	commands.insert_resource(Scoreboard { score: 0 });
+	commands.insert_resource(NextSpawnId::default());
+	*displayed_score = DisplayedScore::default();
+	*tally = WaveTally::default();
+	*hitstop = Hitstop::default();
+	*modifiers = FormationModifiers::default();
+	tainted.0 = false;
+
+	// A fresh seed unless a challenge code picked one
+	let seed = active_challenge.0.map_or_else(clock_seed, |challenge| challenge.seed);
+	*rng = GameRng::from_seed(seed);
+
+	let code = ChallengeCode {
+		seed,
+		assist: assist.enabled,
+		mutators: *mutators,
+		difficulty: config.difficulty,
+	};
+	info!("Starting run, challenge code {}", code.encode());
+	run_code.0 = Some(code);
+}
+
+fn game_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	mutators: Res<Mutators>,
+	config: Res<GameConfig>,
+	enemy_catalog: Res<EnemyCatalog>,
+	strings: Res<Strings>,
+	assist: Res<AssistSettings>,
+	high_score: Res<HighScore>,
+	displayed_score: Res<DisplayedScore>,
+	currency: Res<Currency>,
+	active_challenge: Res<ActiveChallenge>,
+	player_assets: Res<PlayerAssets>,
+	formations: Res<Formations>,
+) {
+	// Which way up the playfield is this run, flipped by the Mirror mutator
+	let orientation = PlayfieldOrientation::from_mutators(&mutators);
+	commands.insert_resource(orientation);
+
+	let mut formation = FormationParams::for_wave(1, &config);
+	commands.insert_resource(RunStats::default());
+
+	// The starting y-position of the player.
+	let player_y: f32 = orientation.world_y(-(HEIGHT / 2.0) + 50.0);
+	commands.insert_resource(PlayerBounds {
+		half_extents: Vec2::splat(PLAYER_SPRITE_SIZE * mutators.player_scale(&config) / 2.0),
+	});
+
+	// Spawn the player sprite, and scale it up (or down with Tiny Player)
+	let player_scale = mutators.player_scale(&config);
+	let player_transform = Transform {
+		translation: Vec3::new(0.0, player_y, 0.0),
+		scale: Vec3::new(player_scale, player_scale, 1.0),
+		..default()
+	};
+	let mut player = commands.spawn((
+		Player,
+		Faction::Player,
+		PlayerFacing::default(),
+		GameEntity,
+		Recoil::default(),
+		CosmeticOffset::default(),
+		// Until the sprite loads and update_player_bounds measures it properly
+		Collider { half_extents: Vec2::splat(PLAYER_SPRITE_SIZE * player_scale / 2.0) },
+		PreviousTransform(Vec3::new(0.0, player_y, 0.0)),
+	));
+
+	// Point the ship at the invaders either way
+	if player_assets.sheet_failed(&asset_server) {
+		player.insert(SpriteBundle {
+			transform: player_transform,
+			texture: player_assets.sprite.clone(),
+			sprite: Sprite {
+				flip_y: mutators.mirror,
+				..default()
+			},
+			..default()
+		});
+	} else {
+		player.insert(SpriteSheetBundle {
+			transform: player_transform,
+			texture_atlas: player_assets.atlas.clone(),
+			sprite: TextureAtlasSprite {
+				index: PlayerAssets::IDLE_FRAME,
+				flip_y: mutators.mirror,
+				..default()
+			},
+			..default()
+		});
+	}
+
+	// The HUD is a bar across the top: score on the left, wave in the middle and
+	// the high score on the right. Each value is its own Text so nothing has to
+	// know which section is which.
+	let hud_style = TextStyle {
+		font: strings.font(&asset_server),
+		font_size: SCOREBOARD_FONT_SIZE,
+		color: theme.scoreboard,
+	};
+	commands
+		.spawn((
+			NodeBundle {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: UiRect {
+						top: Val::Px(5.0),
+						left: Val::Px(5.0),
+						right: Val::Px(5.0),
+						..default()
+					},
+					justify_content: JustifyContent::SpaceBetween,
+					..default()
+				},
+				..default()
+			},
+			GameEntity,
+		))
+		.with_children(|parent| {
+			parent
+				.spawn(hud_slot(JustifyContent::FlexStart))
+				.with_children(|parent| {
+					parent.spawn((TextBundle::from_section(strings.get("hud.score"), hud_style.clone()), HudText));
+					parent.spawn((
+						TextBundle::from_section(format_thousands(displayed_score.value as usize), hud_style.clone()),
+						HudText,
+						ScoreboardText,
+					));
+					parent.spawn((
+						TextBundle::from_section(
+							format!("  {}", strings.format("hud.scrap", &[&currency.0.to_string()])),
+							TextStyle {
+								color: PICKUP_COLOUR,
+								..hud_style.clone()
+							},
+						),
+						CurrencyText,
+					));
+				});
+			parent
+				.spawn(hud_slot(JustifyContent::Center))
+				.with_children(|parent| {
+					parent.spawn((
+						TextBundle::from_section(strings.format("hud.wave", &["1"]), hud_style.clone()),
+						HudText,
+						WaveText,
+					));
+				});
+			parent
+				.spawn(hud_slot(JustifyContent::FlexEnd))
+				.with_children(|parent| {
+					parent.spawn((
+						TextBundle::from_section(
+							high_score.label(&strings),
+							hud_style.clone(),
+						),
+						HudText,
+						HighScoreText,
+					));
+				});
+		});
+
+	// Wave progress, centred under the HUD
+	commands
+		.spawn((
+			NodeBundle {
+				style: Style {
+					position_type: PositionType::Absolute,
+					position: UiRect {
+						top: Val::Px(WAVE_PROGRESS_TOP),
+						left: Val::Px(0.0),
+						right: Val::Px(0.0),
+						..default()
+					},
+					flex_direction: FlexDirection::Column,
+					align_items: AlignItems::Center,
+					..default()
+				},
+				..default()
+			},
+			GameEntity,
+		))
+		.with_children(|parent| {
+			parent
+				.spawn((
+					NodeBundle {
+						style: Style {
+							size: Size::new(Val::Px(WAVE_PROGRESS_WIDTH), Val::Px(WAVE_PROGRESS_HEIGHT)),
+							..default()
+						},
+						background_color: progress_track_colour(&theme).into(),
+						..default()
+					},
+					// So the UI reports when the mouse is over it
+					Interaction::default(),
+					WaveProgressTrack,
+				))
+				.with_children(|parent| {
+					parent.spawn((
+						NodeBundle {
+							style: Style {
+								size: Size::new(Val::Percent(0.0), Val::Percent(100.0)),
+								..default()
+							},
+							background_color: theme.scoreboard.into(),
+							..default()
+						},
+						WaveProgressFill,
+					));
+				});
+			parent.spawn((
+				TextBundle::from_section(
+					"",
+					TextStyle {
+						font: strings.font(&asset_server),
+						font_size: MUTATORS_HUD_FONT_SIZE,
+						color: theme.scoreboard,
+					},
+				),
+				WaveRemainingText,
+			));
+		});
+
+	// List any active mutators (and assist mode) under the scoreboard, with what
+	// they do to the score. A challenge's code goes on the end so it can be shared.
+	let challenge_label = active_challenge
+		.0
+		.map(|challenge| strings.format("hud.challenge", &[&challenge.encode()]));
+	let mut active_mutators: Vec<&str> = mutators
+		.active_names()
+		.into_iter()
+		.map(|key| strings.get(key))
+		.collect();
+	if assist.enabled {
+		active_mutators.push(strings.get("hud.assist"));
+	}
+	if let Some(label) = &challenge_label {
+		active_mutators.push(label);
+	}
+	if !active_mutators.is_empty() {
+		let multiplier = mutators.score_multiplier() * assist.score_multiplier();
+		commands.spawn((
+			TextBundle::from_section(
+				format!("{}  x{:.2}", active_mutators.join(", "), multiplier),
+				TextStyle {
+					font: strings.font(&asset_server),
+					font_size: MUTATORS_HUD_FONT_SIZE,
+					color: theme.scoreboard,
+				},
+			)
+			.with_style(Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Px(WAVE_PROGRESS_TOP + WAVE_PROGRESS_HEIGHT + 3.0),
+					left: Val::Px(5.0),
+					..default()
+				},
+				..default()
+			}),
+			MutatorsHud,
+			GameEntity,
+		));
+	}
+
+	// The event log in the bottom-left. Lines are stacked in a column, so when
+	// one expires the rest close the gap on their own.
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					bottom: Val::Px(40.0),
+					left: Val::Px(5.0),
+					..default()
+				},
+				flex_direction: FlexDirection::Column,
+				..default()
+			},
+			..default()
+		},
+		EventLog,
+		GameEntity,
+	));
+
+	if mutators.bullet_gravity {
+		spawn_arc_preview(&mut commands, &theme);
+	}
+	spawn_descent_arrows(&mut commands, &theme);
+
+	// The first wave is already there when the game starts
+	let layout = formations.for_wave(1);
+	let total = spawn_formation(&mut commands, &theme, &enemy_catalog, &orientation, &mut formation, layout, false);
+	commands.insert_resource(formation);
+	commands.insert_resource(Wave { total, ..default() });
+	spawn_wave_banner(&mut commands, &asset_server, &theme, &strings, 1, total);
+}
+
+// Spawn aliens at the top of the screen.
+fn spawn_formation(
+	commands: &mut Commands,
+	theme: &Theme,
+	enemy_catalog: &EnemyCatalog,
+	orientation: &PlayfieldOrientation,
+	formation: &mut FormationParams,
+	layout: &FormationLayout,
+	fly_in: bool,
+) -> usize {
+	let kind = EnemyKind::Invader;
+	let size = enemy_catalog.get(kind).size();
+
+	// Narrower formations are still centred
+	let grid_width = (layout.columns() - 1) as f32 * ENEMY_SPACING.x;
+	let grid_top = ENEMY_GRID_TOP - formation.start_drop;
+
+	for (row, cells) in layout.cells.iter().enumerate() {
+		for (column, _) in cells.iter().enumerate().filter(|&(_, &filled)| filled) {
+			let x = -(grid_width / 2.0) + column as f32 * ENEMY_SPACING.x;
+			let y = orientation.world_y(grid_top - row as f32 * ENEMY_SPACING.y);
+
+			let target = Vec3::new(x, y, 0.0);
+
+			if !fly_in {
+				commands.spawn((
+					enemy_sprite(theme, size, target),
+					Enemy,
+					Faction::Enemy,
+					GameEntity,
+					kind,
+					Collider { half_extents: size / 2.0 },
+					PreviousTransform(target),
+					CosmeticOffset::default(),
+				));
+				continue;
+			}
+
+			// Each half of the formation swoops in from its own side, across
+			// the top and then down into its slot
+			let side = if x < 0.0 { -1.0 } else { 1.0 };
+			let start = Vec3::new(
+				side * (WIDTH / 2.0 + ENEMY_SIZE.x),
+				orientation.world_y(HEIGHT / 2.0 + ENEMY_SIZE.y),
+				0.0,
+			);
+
+			commands.spawn((
+				enemy_sprite(theme, size, start),
+				Enemy,
+				Faction::Enemy,
+				GameEntity,
+				kind,
+				FlyingIn {
+					start,
+					control: Vec3::new(x, start.y, 0.0),
+					target,
+					progress: 0.0,
+				},
+				PreviousTransform(start),
+				CosmeticOffset::default(),
+			));
+		}
+	}
+
+	formation.enemies = layout.count();
+	formation.enemies
+}
+
+fn enemy_sprite(theme: &Theme, size: Vec2, translation: Vec3) -> SpriteBundle {
+	SpriteBundle {
+		sprite: Sprite {
+			color: theme.enemy,
+			custom_size: Some(size),
+			..default()
+		},
+		transform: Transform::from_translation(translation),
+		..default()
+	}
+}
+
+// Move flying-in enemies along their curve, and drop them into the formation
+// once they get there
+fn fly_in_enemies(
+	mut commands: Commands,
+	enemy_catalog: Res<EnemyCatalog>,
+	mut query: Query<(Entity, &mut Transform, &mut FlyingIn, &EnemyKind)>,
+) {
+	for (entity, mut transform, mut flying_in, kind) in &mut query {
+		flying_in.progress = (flying_in.progress + TIME_STEP / FLY_IN_SECS).min(1.0);
+
+		if flying_in.progress >= 1.0 {
+			transform.translation = flying_in.target;
+			commands.entity(entity)
+				.remove::<FlyingIn>()
+				.insert(Collider { half_extents: enemy_catalog.get(*kind).size() / 2.0 });
+			continue;
+		}
+
+		// Ease out, so they slow down as they settle into place
+		let t = 1.0 - (1.0 - flying_in.progress).powi(2);
+		let from_start = flying_in.start.lerp(flying_in.control, t);
+		let to_target = flying_in.control.lerp(flying_in.target, t);
+		transform.translation = from_start.lerp(to_target, t);
+	}
+}
+
+// Runs down the modifiers' timers, dropping any that have run out
+fn tick_formation_modifiers(mut modifiers: ResMut<FormationModifiers>) {
+	if !modifiers.active.is_empty() {
+		modifiers.tick(std::time::Duration::from_secs_f32(TIME_STEP));
+	}
+}
+
+// Walk the formation sideways, dropping down and turning around at the walls.
+// The walls are checked against whichever enemies are still alive, so a
+// formation with its outer columns shot away still marches all the way across.
+// Also marches the menu's demo formation (T = MenuDemo), which never shares the
+// screen with a real one
+fn march_formation<T: Component>(
+	orientation: Res<PlayfieldOrientation>,
+	config: Res<GameConfig>,
+	assist: Res<AssistSettings>,
+	mutators: Res<Mutators>,
+	modifiers: Res<FormationModifiers>,
+	mut formation: ResMut<FormationParams>,
+	mut query: Query<(&mut Transform, &Collider, Option<&mut PreviousTransform>), With<T>>,
+	flying_in_query: Query<(), With<FlyingIn>>,
+) {
+	formation.time_to_descent = f32::INFINITY;
+
+	// Wait for the whole formation to arrive before it starts marching
+	if !flying_in_query.is_empty() {
+		return;
+	}
+
+	let Some((left, right)) = formation_bounds(
+		query.iter().map(|(transform, collider, _)| (transform.translation.x, collider.half_extents.x))
+	) else {
+		return;
+	};
+
+	let mut alive = 0;
+	for _ in &query {
+		alive += 1;
+	}
+
+	formation.effective_speed = formation.speed_with_survivors(alive, &config, &assist, &modifiers);
+
+	let step = formation.direction * formation.effective_speed * TIME_STEP;
+	let wall = (WIDTH / 2.0) - FORMATION_EDGE_MARGIN;
+	let drop = -orientation.up * config.formation_drop;
+
+	if mutators.wrap {
+		// No walls, each enemy carries on round from the other side once it's
+		// fully off screen. Off screen they can't be hit, since bullets are
+		// gone by then too.
+		for (mut transform, collider, previous) in &mut query {
+			let edge = WIDTH / 2.0 + collider.half_extents.x;
+			let x = transform.translation.x + step;
+			let wrapped = (x + edge).rem_euclid(2.0 * edge) - edge;
+			transform.translation.x = wrapped;
+
+			// Or it'd be drawn sliding across the whole screen for a frame
+			if let Some(mut previous) = previous {
+				previous.0.x += wrapped - x;
+			}
+		}
+
+		formation.lap_distance += step.abs();
+		if formation.lap_distance >= WRAP_LAP_DISTANCE {
+			formation.lap_distance -= WRAP_LAP_DISTANCE;
+			formation.speed_up(&config);
+
+			for (mut transform, _, _) in &mut query {
+				transform.translation += drop.extend(0.0);
+			}
+		}
+
+		let ticks = ((WRAP_LAP_DISTANCE - formation.lap_distance) / step.abs()).ceil();
+		formation.time_to_descent = ticks * TIME_STEP;
+		return;
+	}
+
+	if overshoots_wall(left, right, step, wall) {
+		formation.drop(&config);
+
+		for (mut transform, _, _) in &mut query {
+			transform.translation += drop.extend(0.0);
+		}
+	} else {
+		for (mut transform, _, _) in &mut query {
+			transform.translation.x += step;
+		}
+
+		formation.time_to_descent = ticks_to_descent(left + step, right + step, step, wall) as f32 * TIME_STEP;
+	}
+}
+
+// Whether another step would take the formation past either wall, so it drops
+// instead
+fn overshoots_wall(left: f32, right: f32, step: f32, wall: f32) -> bool {
+	right + step > wall || left + step < -wall
+}
+
+// How many more ticks at this step until the formation overshoots the wall and
+// drops, counting the tick it drops on. Recalculated every tick, so it keeps up
+// as the formation speeds up.
+fn ticks_to_descent(left: f32, right: f32, step: f32, wall: f32) -> u32 {
+	if step == 0.0 {
+		return u32::MAX;
+	}
+
+	let room = if step > 0.0 { wall - right } else { left + wall };
+	(room.max(0.0) / step.abs()).floor() as u32 + 1
+}
+
+// Three thin chevrons down each side, pointing at the wall
+fn spawn_descent_arrows(commands: &mut Commands, theme: &Theme) {
+	for side in [-1.0, 1.0] {
+		for i in 0..DESCENT_ARROWS_PER_SIDE {
+			let y = HEIGHT * ((i + 1) as f32 / (DESCENT_ARROWS_PER_SIDE + 1) as f32 - 0.5);
+			let x = side * (WIDTH / 2.0 - FORMATION_EDGE_MARGIN);
+
+			commands
+				.spawn((
+					SpatialBundle {
+						transform: Transform::from_xyz(x, y, 5.0).with_scale(Vec3::new(side, 1.0, 1.0)),
+						visibility: Visibility::Hidden,
+						..default()
+					},
+					DescentArrow { side },
+					GameEntity,
+				))
+				.with_children(|parent| {
+					// Upper and lower strokes of a >, flipped for the left wall by the scale above
+					for (offset, angle) in [(4.0, 45.0_f32), (-4.0, -45.0_f32)] {
+						parent.spawn(SpriteBundle {
+							sprite: Sprite {
+								color: theme.enemy,
+								custom_size: Some(DESCENT_ARROW_SIZE),
+								..default()
+							},
+							transform: Transform::from_xyz(0.0, offset, 0.0)
+								.with_rotation(Quat::from_rotation_z(angle.to_radians())),
+							..default()
+						});
+					}
+				});
+		}
+	}
+}
+
+// Flash the arrows on the side the formation's about to hit, with a soft tick as
+// the warning starts. With Wrap there's no wall to point at, so there's no warning.
+fn update_descent_warning(
+	time: Res<Time>,
+	settings: Res<Settings>,
+	mutators: Res<Mutators>,
+	formation: Res<FormationParams>,
+	mut sound_events: EventWriter<PlaySound>,
+	mut arrow_query: Query<(&DescentArrow, &mut Visibility)>,
+	mut warned: Local<bool>,
+) {
+	let warning = !mutators.wrap && formation.time_to_descent <= DESCENT_WARNING_SECS;
+	if warning && !*warned {
+		sound_events.send(PlaySound::new(SoundId::UiClick).with_volume(DESCENT_TICK_VOLUME));
+	}
+	*warned = warning;
+
+	// Held steady rather than flashing with reduced motion on
+	let flash_on = settings.reduced_motion
+		|| (time.elapsed_seconds() / DESCENT_WARNING_FLASH_SECS) as u32 % 2 == 0;
+
+	for (arrow, mut visibility) in &mut arrow_query {
+		*visibility = if warning && flash_on && arrow.side == formation.direction {
+			Visibility::Inherited
+		} else {
+			Visibility::Hidden
+		};
+	}
+}
+
+// Once the last enemy is gone, bring in the next wave
+fn next_wave(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	config: Res<GameConfig>,
+	enemy_catalog: Res<EnemyCatalog>,
+	orientation: Res<PlayfieldOrientation>,
+	settings: Res<Settings>,
+	mut wave: ResMut<Wave>,
+	mut formation: ResMut<FormationParams>,
+	finisher: Res<Finisher>,
+	mut run_stats: ResMut<RunStats>,
+	mut tally: ResMut<WaveTally>,
+	strings: Res<Strings>,
+	formations: Res<Formations>,
+	mut messages: EventWriter<GameMessage>,
+	enemy_query: Query<(), With<Enemy>>,
+) {
+	if !enemy_query.is_empty() || finisher.timer.is_some() {
+		return;
+	}
+
+	if !tally.finished {
+		if tally.timer.is_none() {
+			messages.send(GameMessage(strings.format("hud.wave_cleared", &[&wave.number.to_string()])));
+			start_wave_tally(&mut commands, &asset_server, &strings, &mut tally, run_stats.bonuses(&strings));
+		}
+		return;
+	}
+
+	*tally = WaveTally::default();
+	*run_stats = RunStats::default();
+
+	wave.number += 1;
+	wave.killed = 0;
+	*formation = FormationParams::for_wave(wave.number, &config);
+	wave.total = spawn_formation(
+		&mut commands,
+		&theme,
+		&enemy_catalog,
+		&orientation,
+		&mut formation,
+		formations.for_wave(wave.number),
+		settings.fly_in,
+	);
+	spawn_wave_banner(&mut commands, &asset_server, &theme, &strings, wave.number, wave.total);
+}
+
+fn start_wave_tally(
+	commands: &mut Commands,
+	asset_server: &AssetServer,
+	strings: &Strings,
+	tally: &mut WaveTally,
+	lines: Vec<BonusLine>,
+) {
+	// Nothing earned, straight on to the next wave
+	if lines.is_empty() {
+		tally.finished = true;
+		return;
+	}
+
+	tally.lines = lines;
+	tally.timer = Some(Timer::from_seconds(WAVE_TALLY_LINE_SECS, TimerMode::Repeating));
+
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Percent(45.0),
+					..default()
+				},
+				size: Size::width(Val::Percent(100.0)),
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			..default()
+		},
+		WaveTallyUi,
+	))
+	.with_children(|parent| {
+		parent.spawn(TextBundle::from_section(
+			strings.get("tally.title"),
+			TextStyle {
+				font: strings.font(asset_server),
+				font_size: WAVE_TALLY_FONT_SIZE,
+				color: Color::WHITE,
+			},
+		));
+	});
+}
+
+// Land the bonus lines one at a time, adding each to the score as it appears
+fn update_wave_tally(
+	mut commands: Commands,
+	time: Res<Time>,
+	theme: Res<Theme>,
+	asset_server: Res<AssetServer>,
+	mutators: Res<Mutators>,
+	assist: Res<AssistSettings>,
+	strings: Res<Strings>,
+	mut sound_events: EventWriter<PlaySound>,
+	mut scoreboard: ResMut<Scoreboard>,
+	mut tally: ResMut<WaveTally>,
+	ui_query: Query<Entity, With<WaveTallyUi>>,
+) {
+	let Some(timer) = tally.timer.as_mut() else {
+		return;
+	};
+
+	if !timer.tick(time.delta()).just_finished() {
+		return;
+	}
+
+	let Ok(ui) = ui_query.get_single() else {
+		return;
+	};
+
+	// Everything's been shown and held for a moment
+	if tally.shown == tally.lines.len() {
+		commands.entity(ui).despawn_recursive();
+		tally.timer = None;
+		tally.finished = true;
+		return;
+	}
+
+	let line = &tally.lines[tally.shown];
+	award_points(&mut scoreboard, line.points, &mutators, &assist);
+	if line.fanfare {
+		sound_events.send(PlaySound::new(SoundId::Fanfare));
+	}
+
+	let text = commands
+		.spawn(TextBundle::from_section(
+			format!("{}  +{}", line.label, format_thousands(line.points)),
+			TextStyle {
+				font: strings.font(&asset_server),
+				font_size: WAVE_TALLY_FONT_SIZE,
+				color: theme.scoreboard,
+			},
+		))
+		.id();
+	commands.entity(ui).add_child(text);
+
+	tally.shown += 1;
+}
+
+fn wave_tally_cleanup(
+	mut commands: Commands,
+	mut tally: ResMut<WaveTally>,
+	query: Query<Entity, With<WaveTallyUi>>,
+) {
+	for entity in &query {
+		commands.entity(entity).despawn_recursive();
+	}
+
+	*tally = WaveTally::default();
+}
+
+// "WAVE 3" across the middle of the screen, with what's in it underneath
+fn spawn_wave_banner(
+	commands: &mut Commands,
+	asset_server: &AssetServer,
+	theme: &Theme,
+	strings: &Strings,
+	wave: u32,
+	enemies: usize,
+) {
+	let font = strings.font(asset_server);
+	let subtitle = WAVE_SUBTITLES
+		.iter()
+		.find(|(number, _)| *number == wave)
+		.map(|(_, subtitle)| strings.get(subtitle));
+
+	let banner = spawn_banner(commands, &font, theme, &strings.format("wave.title", &[&wave.to_string()]), subtitle);
+
+	// Every wave is the same kind of enemy for now, so the preview is just the one icon
+	commands.entity(banner).with_children(|parent| {
+		parent.spawn(NodeBundle {
+			style: Style {
+				align_items: AlignItems::Center,
+				margin: UiRect::top(Val::Px(10.0)),
+				..default()
+			},
+			..default()
+		})
+		.with_children(|parent| {
+			parent.spawn((
+				NodeBundle {
+					style: Style {
+						size: Size::new(Val::Px(ENEMY_SIZE.x / 2.0), Val::Px(ENEMY_SIZE.y / 2.0)),
+						margin: UiRect::right(Val::Px(8.0)),
+						..default()
+					},
+					background_color: theme.enemy.into(),
+					..default()
+				},
+				WaveBannerPart,
+			));
+
+			parent.spawn((
+				TextBundle::from_section(
+					format!("x{enemies}"),
+					TextStyle {
+						font: font.clone(),
+						font_size: WAVE_BANNER_SUBTITLE_FONT_SIZE,
+						color: theme.scoreboard,
+					},
+				),
+				WaveBannerPart,
+			));
+		});
+	});
+}
+
+// A big title (and maybe a subtitle) that fades in and out in the middle of the
+// screen, for new waves and anything else worth shouting about
+fn spawn_banner(
+	commands: &mut Commands,
+	font: &Handle<Font>,
+	theme: &Theme,
+	title: &str,
+	subtitle: Option<&str>,
+) -> Entity {
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Percent(30.0),
+					..default()
+				},
+				size: Size::width(Val::Percent(100.0)),
+				flex_direction: FlexDirection::Column,
+				align_items: AlignItems::Center,
+				..default()
+			},
+			..default()
+		},
+		WaveBanner {
+			timer: Timer::from_seconds(WAVE_BANNER_SECS, TimerMode::Once),
+		},
+	))
+	.with_children(|parent| {
+		parent.spawn((
+			TextBundle::from_section(
+				title,
+				TextStyle {
+					font: font.clone(),
+					font_size: WAVE_BANNER_FONT_SIZE,
+					color: theme.scoreboard,
+				},
+			),
+			WaveBannerPart,
+		));
+
+		if let Some(subtitle) = subtitle {
+			parent.spawn((
+				TextBundle::from_section(
+					subtitle,
+					TextStyle {
+						font: font.clone(),
+						font_size: WAVE_BANNER_SUBTITLE_FONT_SIZE,
+						color: theme.scoreboard,
+					},
+				),
+				WaveBannerPart,
+			));
+		}
+	})
+	.id()
+}
+
+// Slide the banner down into place while it fades in, then fade it back out
+fn update_wave_banner(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut banner_query: Query<(Entity, &mut WaveBanner, &mut Style)>,
+	mut part_query: Query<(Option<&mut Text>, Option<&mut BackgroundColor>), With<WaveBannerPart>>,
+) {
+	// Normally there's only one, but waves can be cleared faster than a banner lasts
+	let mut alpha = None;
+
+	for (banner, mut state, mut style) in &mut banner_query {
+		state.timer.tick(time.delta());
+		if state.timer.finished() {
+			commands.entity(banner).despawn_recursive();
+			continue;
+		}
+
+		let fade_in = state.timer.elapsed_secs() / WAVE_BANNER_FADE_SECS;
+		let fade_out = state.timer.remaining_secs() / WAVE_BANNER_FADE_SECS;
+		alpha = Some(fade_in.min(fade_out).min(1.0));
+
+		style.position.top = Val::Percent(30.0 - 5.0 * (1.0 - fade_in.min(1.0)));
+	}
+
+	let Some(alpha) = alpha else {
+		return;
+	};
+
+	for (text, background) in &mut part_query {
+		if let Some(mut text) = text {
+			for section in &mut text.sections {
+				section.style.color.set_a(alpha);
+			}
+		}
+
+		if let Some(mut background) = background {
+			background.0.set_a(alpha);
+		}
+	}
+}
+
+fn wave_banner_cleanup(mut commands: Commands, query: Query<Entity, With<WaveBanner>>) {
+	for entity in &query {
+		commands.entity(entity).despawn_recursive();
+	}
+}
+
+// The left and right edges of the live formation, from each enemy's x and half width
+fn formation_bounds(enemies: impl Iterator<Item = (f32, f32)>) -> Option<(f32, f32)> {
+	enemies.fold(None, |bounds, (x, half_width)| {
+		let (left, right) = bounds.unwrap_or((f32::MAX, f32::MIN));
+		Some((left.min(x - half_width), right.max(x + half_width)))
+	})
+}
+
+// Measure the player ship once its image has loaded (or straight away, if it
+// already had), so swapping the art or its scale never breaks the geometry.
+// The sprite sheet's frames are a fixed size, so only the fallback image needs
+// measuring.
+fn update_player_bounds(
+	images: Res<Assets<Image>>,
+	mut image_events: EventReader<AssetEvent<Image>>,
+	mut bounds: ResMut<PlayerBounds>,
+	mut query: Query<(Option<Ref<Handle<Image>>>, &Transform, &mut Collider, Ref<Player>)>,
+) {
+	let Ok((texture, transform, mut collider, player)) = query.get_single_mut() else {
+		image_events.clear();
+		return;
+	};
+
+	let loaded = image_events.iter().any(|event| match event {
+		AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+			texture.as_ref().map_or(false, |texture| handle == &**texture)
+		},
+		AssetEvent::Removed { .. } => false,
+	});
+
+	// The fallback image is added after the player, if the sheet fails to load
+	let swapped = texture.as_ref().map_or(false, |texture| texture.is_added());
+
+	if !loaded && !swapped && !player.is_added() {
+		return;
+	}
+
+	let size = match &texture {
+		Some(texture) => {
+			let Some(image) = images.get(&**texture) else {
+				return;
+			};
+			image.size()
+		},
+		None => PlayerAssets::FRAME_SIZE,
+	};
+
+	bounds.half_extents = size * transform.scale.truncate().abs() / 2.0;
+	collider.half_extents = bounds.half_extents;
+}
+
+// Swap a ship that was spawned before its sheet failed to load over to the
+// single image
+fn fall_back_to_player_sprite(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+	player_assets: Res<PlayerAssets>,
+	query: Query<(Entity, &TextureAtlasSprite), With<Player>>,
+) {
+	if query.is_empty() || !player_assets.sheet_failed(&asset_server) {
+		return;
+	}
+
+	for (entity, sprite) in &query {
+		warn!("Couldn't load {}, the player ship won't bank", PlayerAssets::SHEET_PATH);
+
+		commands
+			.entity(entity)
+			.remove::<(Handle<TextureAtlas>, TextureAtlasSprite)>()
+			.insert((
+				player_assets.sprite.clone(),
+				Sprite {
+					flip_y: sprite.flip_y,
+					..default()
+				},
+			));
+	}
+}
+
+// Bank the ship towards the way it's moving. Only ever changes the frame, so
+// anything blinking the ship through its Visibility is left alone.
+fn update_player_frame(mut query: Query<(&PlayerFacing, &mut TextureAtlasSprite), With<Player>>) {
+	for (facing, mut sprite) in &mut query {
+		let frame = if facing.0 < 0.0 {
+			PlayerAssets::BANK_LEFT_FRAME
+		} else if facing.0 > 0.0 {
+			PlayerAssets::BANK_RIGHT_FRAME
+		} else {
+			PlayerAssets::IDLE_FRAME
+		};
+
+		if sprite.index != frame {
+			sprite.index = frame;
+		}
+	}
+}
+
+fn move_player(
+	keyboard_input: Res<Input<KeyCode>>,
+
+	bounds: Res<PlayerBounds>,
+
+	// Get the transform properties of each Player component
+	mut query: Query<(&mut Transform, &mut PlayerFacing), With<Player>>,
+) {
+	let Ok((mut player_transform, mut facing)) = query.get_single_mut() else {
+		return;
+	};
+	let mut direction = 0.0;
+
+	if keyboard_input.pressed(KeyCode::Left) {
+		direction -= 1.0;
+	}
+
+	if keyboard_input.pressed(KeyCode::Right) {
+		direction += 1.0;
+	}
+
+	let new_position = player_transform.translation.x + direction * PLAYER_SPEED * TIME_STEP;
+
+	let left_bound = -(WIDTH / 2.0) + bounds.half_extents.x;
+	let right_bound = (WIDTH / 2.0) - bounds.half_extents.x;
+
+	player_transform.translation.x = new_position.clamp(left_bound, right_bound);
+	facing.0 = direction;
+}
+
+// Shooting is a pipeline: input asks to fire, fire_weapon decides whether a
+// shot happens and spawns it, and everything that reacts to a shot (sound,
+// recoil, score) reads ShotFired. New weapons only need to change the middle.
+fn read_fire_input(keyboard_input: Res<Input<KeyCode>>, mut intents: EventWriter<FireIntent>) {
+	if keyboard_input.just_pressed(KeyCode::Space) {
+		intents.send_default();
+	}
+}
+
+fn fire_weapon(
+	mut commands: Commands,
+	mut intents: EventReader<FireIntent>,
+	mut shots: EventWriter<ShotFired>,
+	asset_server: Res<AssetServer>,
+	bullet_assets: Res<BulletAssets>,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	mutators: Res<Mutators>,
+	orientation: Res<PlayfieldOrientation>,
+	bounds: Res<PlayerBounds>,
+	player_query: Query<&Transform, With<Player>>,
+) {
+	let Ok(player_transform) = player_query.get_single() else {
+		intents.clear();
+		return;
+	};
+	let bullet_spawn_pos = muzzle_position(player_transform.translation, &orientation, &bounds);
+
+	for _ in intents.iter() {
+		// Drawn at BULLET_SIZE whatever size the image is, so it always matches the Collider
+		let mut bullet = commands.spawn((
+			Bullet,
+			Faction::Player,
+			GameEntity,
+			Collider { half_extents: BULLET_SIZE / 2.0 },
+			Velocity(orientation.up * BULLET_SPEED),
+			PreviousTransform(bullet_spawn_pos),
+		));
+
+		if asset_server.get_load_state(&bullet_assets.sprite) == LoadState::Failed {
+			bullet.insert(MaterialMesh2dBundle {
+				mesh: bullet_assets.mesh.clone(),
+				material: bullet_assets.material.clone(),
+				transform: Transform::from_translation(bullet_spawn_pos),
+				..default()
+			});
+		} else {
+			bullet.insert(SpriteBundle {
+				texture: bullet_assets.sprite.clone(),
+				sprite: Sprite {
+					color: Faction::Player.bullet_colour(&theme, &settings),
+					custom_size: Some(BULLET_SIZE),
+					flip_y: mutators.mirror,
+					..default()
+				},
+				transform: Transform::from_translation(bullet_spawn_pos),
+				..default()
+			});
+		}
+
+		if mutators.ricochet {
+			bullet.insert(Ricochet { bounces_left: RICOCHET_BOUNCES });
+		}
+
+		shots.send(ShotFired { position: bullet_spawn_pos });
+	}
+}
+
+fn play_shot_sounds(mut shots: EventReader<ShotFired>, mut sound_events: EventWriter<PlaySound>) {
+	for _ in shots.iter() {
+		sound_events.send(PlaySound::new(SoundId::Shoot).with_pitch_variance(SHOOT_PITCH_VARIANCE));
+	}
+}
+
+// Kick the ship back and flash at the muzzle
+fn shot_feedback(
+	mut commands: Commands,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	mut shots: EventReader<ShotFired>,
+	mut player_query: Query<&mut Recoil, With<Player>>,
+) {
+	if settings.reduced_motion {
+		shots.clear();
+		return;
+	}
+
+	for shot in shots.iter() {
+		for mut recoil in &mut player_query {
+			recoil.0.reset();
+		}
+
+		commands.spawn((
+			SpriteBundle {
+				sprite: Sprite {
+					color: Faction::Player.bullet_colour(&theme, &settings),
+					custom_size: Some(MUZZLE_FLASH_SIZE),
+					..default()
+				},
+				// In front of the bullet
+				transform: Transform::from_translation(shot.position + Vec3::Z * 0.1),
+				..default()
+			},
+			MuzzleFlash {
+				timer: Timer::from_seconds(MUZZLE_FLASH_SECS, TimerMode::Once),
+			},
+			GameEntity,
+		));
+	}
+}
+
+// A point for every shot, as it's always been
+fn award_shot_points(
+	mut shots: EventReader<ShotFired>,
+	mutators: Res<Mutators>,
+	assist: Res<AssistSettings>,
+	mut scoreboard: ResMut<Scoreboard>,
+) {
+	for _ in shots.iter() {
+		award_points(&mut scoreboard, 1, &mutators, &assist);
+	}
+}
+
+// Fire from the ship's nose rather than its middle
+fn muzzle_position(player: Vec3, orientation: &PlayfieldOrientation, bounds: &PlayerBounds) -> Vec3 {
+	(player.truncate() + orientation.up * bounds.half_extents.y).extend(0.0)
+}
+
+// Number anything new in the order it appeared. Things that turn up in the same
+// tick are ordered by where they are, which is the same every replay.
+fn assign_spawn_ids(
+	mut commands: Commands,
+	mut next_spawn_id: ResMut<NextSpawnId>,
+	query: Query<
+		(Entity, &Transform),
+		(Without<SpawnId>, Or<(With<Player>, With<Bullet>, With<Enemy>, With<Pickup>)>),
+	>,
+) {
+	let mut new: Vec<(Entity, Vec3)> = query
+		.iter()
+		.map(|(entity, transform)| (entity, transform.translation))
+		.collect();
+	new.sort_by(|(_, a), (_, b)| a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y)));
+
+	for (entity, _) in new {
+		commands.entity(entity).insert(SpawnId(next_spawn_id.0));
+		next_spawn_id.0 += 1;
+	}
+}
+
+// Snap everything gameplay cares about to POSITION_GRID. Only the logical
+// Transform, what's drawn is still interpolated smoothly between ticks.
+fn quantise_positions(mut query: Query<&mut Transform, With<SpawnId>>) {
+	for mut transform in &mut query {
+		let snapped = (transform.translation.truncate() * POSITION_GRID).round() / POSITION_GRID;
+
+		// Don't trip change detection for things that didn't move
+		if snapped != transform.translation.truncate() {
+			transform.translation = snapped.extend(transform.translation.z);
+		}
+	}
+}
+
+fn store_previous_transforms(mut query: Query<(&Transform, &mut PreviousTransform)>) {
+	for (transform, mut previous) in &mut query {
+		previous.0 = transform.translation;
+	}
+}
+
+// Draw moving things between their last two fixed tick positions, plus any
+// cosmetic offset. Only the GlobalTransform is touched, so gameplay never sees
+// the drawn position. Anything using this has no parent or children, so
+// there's nothing to re-propagate.
+fn interpolate_transforms(
+	config: Res<GameConfig>,
+	fixed_time: Res<FixedTime>,
+	mut query: Query<(&Transform, &PreviousTransform, Option<&CosmeticOffset>, &mut GlobalTransform)>,
+) {
+	// With interpolation off, things are drawn exactly where the last tick left them
+	let alpha = if config.interpolate_transforms {
+		(fixed_time.accumulated().as_secs_f32() / fixed_time.period.as_secs_f32()).min(1.0)
+	} else {
+		1.0
+	};
+
+	for (transform, previous, offset, mut global_transform) in &mut query {
+		let mut drawn = Transform {
+			translation: previous.0.lerp(transform.translation, alpha),
+			..*transform
+		};
+
+		if let Some(offset) = offset {
+			drawn.translation += offset.translation;
+			drawn.rotation *= Quat::from_rotation_z(offset.rotation);
+		}
+
+		*global_transform = drawn.into();
+	}
+}
+
+// Bob each enemy in the formation on its own phase and lean them all into the
+// march. The offsets are worked out fresh each frame from the clock, so
+// nothing builds up over a long wave.
+fn animate_formation(
+	time: Res<Time>,
+	settings: Res<Settings>,
+	formation: Res<FormationParams>,
+	mut bank: Local<f32>,
+	mut query: Query<(Entity, &mut CosmeticOffset), (With<Enemy>, Without<FlyingIn>)>,
+) {
+	if settings.reduced_motion {
+		*bank = 0.0;
+		for (_, mut offset) in &mut query {
+			*offset = CosmeticOffset::default();
+		}
+		return;
+	}
+
+	// Moving right leans clockwise
+	let target_bank = -formation.direction * FORMATION_BANK_DEGREES.to_radians();
+	*bank += (target_bank - *bank) * (FORMATION_BANK_SMOOTHING * time.delta_seconds()).min(1.0);
+
+	let elapsed = time.elapsed_seconds_wrapped();
+	for (entity, mut offset) in &mut query {
+		let phase = entity.index() as f32 * 0.7;
+
+		offset.translation.y = (elapsed * FORMATION_BOB_SPEED + phase).sin() * FORMATION_BOB_HEIGHT;
+		offset.rotation = *bank;
+	}
+}
+
+// Push the drawn ship back along its firing direction while it's recoiling.
+// Only the CosmeticOffset moves, so the hitbox and bounds stay put.
+fn update_recoil(
+	time: Res<Time>,
+	orientation: Res<PlayfieldOrientation>,
+	mut query: Query<(&mut Recoil, &mut CosmeticOffset), With<Player>>,
+) {
+	for (mut recoil, mut offset) in &mut query {
+		recoil.0.tick(time.delta());
+
+		offset.translation = if recoil.0.finished() {
+			Vec3::ZERO
+		} else {
+			(-orientation.up * RECOIL_DISTANCE).extend(0.0)
+		};
+	}
+}
+
+fn update_muzzle_flashes(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut query: Query<(Entity, &mut MuzzleFlash)>,
+) {
+	for (entity, mut flash) in &mut query {
+		if flash.timer.tick(time.delta()).finished() {
+			commands.entity(entity).despawn();
+		}
+	}
+}
+
+fn spawn_bullet_trails(
+	mut commands: Commands,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	mut tick: Local<u32>,
+	bullet_query: Query<(Entity, &Transform), With<Bullet>>,
+	segment_query: Query<(), With<TrailSegment>>,
+) {
+	*tick = (*tick + 1) % TRAIL_TICKS;
+	if *tick != 0 || settings.reduced_motion {
+		return;
+	}
+
+	let mut segments = 0;
+	for _ in &segment_query {
+		segments += 1;
+	}
+
+	let mut colour = Faction::Player.bullet_colour(&theme, &settings);
+	colour.set_a(TRAIL_ALPHA);
+
+	for (bullet, transform) in &bullet_query {
+		if segments >= TRAIL_MAX_SEGMENTS {
+			break;
+		}
+
+		commands.spawn((
+			SpriteBundle {
+				sprite: Sprite {
+					color: colour,
+					custom_size: Some(BULLET_SIZE),
+					..default()
+				},
+				// Just behind the bullet itself
+				transform: Transform::from_translation(transform.translation - Vec3::Z * 0.1),
+				..default()
+			},
+			TrailSegment {
+				bullet,
+				timer: Timer::from_seconds(TRAIL_SECS, TimerMode::Once),
+			},
+			GameEntity,
+		));
+		segments += 1;
+	}
+}
+
+// Fade and shrink trail segments, and clear away any whose bullet is gone
+fn update_bullet_trails(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut segment_query: Query<(Entity, &mut TrailSegment, &mut Sprite, &mut Transform)>,
+	bullet_query: Query<(), With<Bullet>>,
+) {
+	for (entity, mut segment, mut sprite, mut transform) in &mut segment_query {
+		segment.timer.tick(time.delta());
+
+		if segment.timer.finished() || !bullet_query.contains(segment.bullet) {
+			commands.entity(entity).despawn();
+			continue;
+		}
+
+		let remaining = segment.timer.percent_left();
+		sprite.color.set_a(TRAIL_ALPHA * remaining);
+		transform.scale = Vec3::new(remaining, remaining, 1.0);
+	}
+}
+
+// A tick of Bullet Gravity. The arc preview steps through the same two functions
+// as the real bullets, so it always matches where they actually go.
+fn gravity_step(velocity: Vec2, orientation: &PlayfieldOrientation) -> Vec2 {
+	velocity - orientation.up * BULLET_GRAVITY * TIME_STEP
+}
+
+fn velocity_step(translation: Vec3, velocity: Vec2) -> Vec3 {
+	translation + (velocity * TIME_STEP).extend(0.0)
+}
+
+fn apply_bullet_gravity(
+	orientation: Res<PlayfieldOrientation>,
+	mut query: Query<&mut Velocity, With<Bullet>>,
+) {
+	for mut velocity in &mut query {
+		velocity.0 = gravity_step(velocity.0, &orientation);
+	}
+}
+
+// For bullets
+fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>) {
+	for (mut transform, velocity) in &mut query {
+		transform.translation = velocity_step(transform.translation, velocity.0);
+	}
+}
+
+fn apply_angular_velocity(mut query: Query<(&mut Transform, &AngularVelocity)>) {
+	for (mut transform, angular_velocity) in &mut query {
+		transform.rotate_z(angular_velocity.0 * TIME_STEP);
+	}
+}
+
+// Only spawned with Bullet Gravity on. The dots stay put and get moved along
+// the predicted arc every frame.
+fn spawn_arc_preview(commands: &mut Commands, theme: &Theme) {
+	let mut colour = theme.bullet;
+	colour.set_a(ARC_PREVIEW_ALPHA);
+
+	for _ in 0..ARC_PREVIEW_DOTS {
+		commands.spawn((
+			SpriteBundle {
+				sprite: Sprite {
+					color: colour,
+					custom_size: Some(Vec2::splat(ARC_PREVIEW_DOT_SIZE)),
+					..default()
+				},
+				visibility: Visibility::Hidden,
+				..default()
+			},
+			ArcPreviewDot,
+			GameEntity,
+		));
+	}
+}
+
+fn update_arc_preview(
+	orientation: Res<PlayfieldOrientation>,
+	bounds: Res<PlayerBounds>,
+	player_query: Query<&Transform, (With<Player>, Without<ArcPreviewDot>)>,
+	mut dot_query: Query<(&mut Transform, &mut Visibility), With<ArcPreviewDot>>,
+) {
+	let Ok(player_transform) = player_query.get_single() else {
+		for (_, mut visibility) in &mut dot_query {
+			*visibility = Visibility::Hidden;
+		}
+		return;
+	};
+
+	let mut position = muzzle_position(player_transform.translation, &orientation, &bounds);
+	let mut velocity = orientation.up * BULLET_SPEED;
+	let mut landed = false;
+
+	for (mut transform, mut visibility) in &mut dot_query {
+		for _ in 0..ARC_PREVIEW_TICKS_PER_DOT {
+			velocity = gravity_step(velocity, &orientation);
+			position = velocity_step(position, velocity);
+		}
+		// Nothing past the edge of the playfield, where the bullet would be cleaned up
+		landed |= position.x.abs() > WIDTH / 2.0 || position.y.abs() > HEIGHT / 2.0;
+
+		transform.translation = position;
+		*visibility = if landed { Visibility::Hidden } else { Visibility::Inherited };
+	}
+}
+
+// Bounce Ricochet bullets off the side walls. The bullet is put back inside by
+// however far it overshot, so a fast one can't end up past the wall and get
+// cleaned up as offscreen.
+fn ricochet_bullets(
+	mut commands: Commands,
+	mut query: Query<(Entity, &mut Transform, &Collider, &mut Velocity, &mut Ricochet)>,
+) {
+	for (entity, mut transform, collider, mut velocity, mut ricochet) in &mut query {
+		let wall = WIDTH / 2.0 - collider.half_extents.x;
+		let x = transform.translation.x;
+
+		let heading_out = (x < -wall && velocity.x < 0.0) || (x > wall && velocity.x > 0.0);
+		if !heading_out {
+			continue;
+		}
+
+		let side = x.signum();
+		transform.translation.x = side * wall - (x - side * wall);
+		velocity.x = -velocity.x;
+		velocity.0 *= RICOCHET_SPEED_BOOST;
+
+		ricochet.bounces_left -= 1;
+		if ricochet.bounces_left == 0 {
+			commands.entity(entity).remove::<Ricochet>();
+		}
+	}
+}
+
+fn award_points(scoreboard: &mut Scoreboard, points: usize, mutators: &Mutators, assist: &AssistSettings) {
+	let multiplier = mutators.score_multiplier() * assist.score_multiplier();
+	scoreboard.score += (points as f32 * multiplier).round() as usize;
+}
+
+// Every enemy death goes through here, whatever caused it, so anything that
+// reacts to kills sees all of them.
+fn kill_enemy(
+	commands: &mut Commands,
+	killed_events: &mut EventWriter<EnemyKilledEvent>,
+	entity: Entity,
+	kind: EnemyKind,
+	position: Vec3,
+) {
+	// Take it out of the game straight away, but leave the sprite around for
+	// the hit flash
+	commands.entity(entity)
+		.remove::<(Enemy, Collider, FlyingIn)>()
+		.insert(HitFlash { shown: false });
+	killed_events.send(EnemyKilledEvent { kind, position });
+}
+
+fn flash_killed_enemies(
+	mut commands: Commands,
+	mut query: Query<(Entity, &mut Sprite, &mut HitFlash)>,
+) {
+	for (entity, mut sprite, mut flash) in &mut query {
+		if flash.shown {
+			commands.entity(entity).despawn_recursive();
+		} else {
+			sprite.color = Color::WHITE;
+			flash.shown = true;
+		}
+	}
+}
+
+fn spawn_debris(
+	mut commands: Commands,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	orientation: Res<PlayfieldOrientation>,
+	mut killed_events: EventReader<EnemyKilledEvent>,
+	mut spawned: Local<Vec<Entity>>,
+	debris_query: Query<(), With<Debris>>,
+) {
+	if settings.reduced_motion {
+		killed_events.clear();
+		return;
+	}
+
+	// Forget any that have already faded out
+	spawned.retain(|&entity| debris_query.contains(entity));
+
+	for event in killed_events.iter() {
+		// Not random, but kills are spread out enough that it doesn't show
+		let seed = event.position.x.abs() as usize + event.position.y.abs() as usize;
+		let pieces = 2 + seed % 2;
+
+		for piece in 0..pieces {
+			if spawned.len() >= DEBRIS_MAX {
+				let oldest = spawned.remove(0);
+				commands.entity(oldest).despawn();
+			}
+
+			let side = piece as f32 / (pieces - 1) as f32 * 2.0 - 1.0;
+			let velocity = Vec2::new(side * DEBRIS_SPREAD_SPEED, 0.0) - orientation.up * DEBRIS_FALL_SPEED;
+
+			let debris = commands
+				.spawn((
+					SpriteBundle {
+						sprite: Sprite {
+							color: theme.enemy,
+							custom_size: Some(Vec2::splat(DEBRIS_SIZE)),
+							..default()
+						},
+						transform: Transform::from_translation(event.position),
+						..default()
+					},
+					Debris {
+						timer: Timer::from_seconds(DEBRIS_SECS, TimerMode::Once),
+					},
+					Velocity(velocity),
+					AngularVelocity(if piece % 2 == 0 { DEBRIS_SPIN } else { -DEBRIS_SPIN }),
+					PreviousTransform(event.position),
+					GameEntity,
+				))
+				.id();
+			spawned.push(debris);
+		}
+	}
+}
+
+fn update_debris(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut query: Query<(Entity, &mut Debris, &mut Sprite)>,
+) {
+	for (entity, mut debris, mut sprite) in &mut query {
+		if debris.timer.tick(time.delta()).finished() {
+			commands.entity(entity).despawn();
+			continue;
+		}
+
+		sprite.color.set_a(debris.timer.percent_left());
+	}
+}
+
+fn not_in_hitstop(hitstop: Res<Hitstop>) -> bool {
+	!hitstop.frozen
+}
+
+// Runs at the start of the tick to decide whether this tick is frozen
+fn update_hitstop(mut hitstop: ResMut<Hitstop>) {
+	hitstop.frozen = hitstop.ticks > 0;
+	hitstop.ticks = hitstop.ticks.saturating_sub(1);
+}
+
+fn trigger_hitstop(
+	mut killed_events: EventReader<EnemyKilledEvent>,
+	settings: Res<Settings>,
+	mut hitstop: ResMut<Hitstop>,
+) {
+	if !settings.hitstop {
+		killed_events.clear();
+		return;
+	}
+
+	// Each kill adds to the freeze, but a whole row dying at once shouldn't
+	// stop the game for a second
+	let kills = killed_events.iter().count() as u32;
+	if kills > 0 {
+		hitstop.ticks = (hitstop.ticks + kills * HITSTOP_TICKS).min(HITSTOP_MAX_TICKS);
+	}
+}
+
+// Kills are only taken out of the enemy query once the tick's commands are
+// applied, so the last one is spotted by comparing the two counts
+fn trigger_finisher(
+	settings: Res<Settings>,
+	mut finisher: ResMut<Finisher>,
+	mut killed_events: EventReader<EnemyKilledEvent>,
+	enemy_query: Query<(), With<Enemy>>,
+) {
+	let mut kills = 0;
+	let mut last_position = None;
+	for event in killed_events.iter() {
+		kills += 1;
+		last_position = Some(event.position);
+	}
+
+	let Some(position) = last_position else {
+		return;
+	};
+
+	let mut remaining = 0;
+	for _ in &enemy_query {
+		remaining += 1;
+	}
+
+	if !settings.finisher || finisher.timer.is_some() || remaining > kills {
+		return;
+	}
+
+	finisher.timer = Some(Timer::from_seconds(FINISHER_SECS, TimerMode::Once));
+	finisher.focus = position.truncate();
+}
+
+// Slow down and zoom in, then ease back out to where the camera started
+fn update_finisher(
+	time: Res<Time>,
+	mut finisher: ResMut<Finisher>,
+	mut game_speed: ResMut<GameSpeed>,
+	mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+	let Some(timer) = finisher.timer.as_mut() else {
+		return;
+	};
+
+	// Real time, not game time, or the slow motion would slow itself down
+	timer.tick(time.delta());
+	let finished = timer.finished();
+	let progress = timer.percent();
+
+	let Ok(mut camera_transform) = camera_query.get_single_mut() else {
+		return;
+	};
+
+	let home = *finisher.camera_home.get_or_insert(*camera_transform);
+
+	if finished {
+		*camera_transform = home;
+		game_speed.0 = 1.0;
+		finisher.timer = None;
+		finisher.camera_home = None;
+		return;
+	}
+
+	// 0 -> 1 -> 0 over the length of the effect
+	let zoom = (progress * std::f32::consts::PI).sin();
+
+	game_speed.0 = FINISHER_SPEED;
+	camera_transform.translation = home.translation + (finisher.focus * zoom).extend(0.0);
+	camera_transform.scale = home.scale * (1.0 + (FINISHER_ZOOM - 1.0) * zoom);
+}
+
+// Leaving mid-finisher (or the state changing under it) shouldn't leave the game slowed down or zoomed
+fn stop_finisher(
+	mut finisher: ResMut<Finisher>,
+	mut game_speed: ResMut<GameSpeed>,
+	mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+	if let (Some(home), Ok(mut camera_transform)) = (finisher.camera_home, camera_query.get_single_mut()) {
+		*camera_transform = home;
+	}
+
+	*finisher = Finisher::default();
+	game_speed.0 = 1.0;
+}
+
+// Slow motion is done by stretching the fixed tick rather than shrinking every
+// step, interpolation keeps it smooth in between
+fn apply_game_speed(game_speed: Res<GameSpeed>, mut fixed_time: ResMut<FixedTime>) {
+	if game_speed.is_changed() {
+		fixed_time.period = std::time::Duration::from_secs_f32(TIME_STEP / game_speed.0);
+	}
+}
+
+fn update_scoreboard(
+	time: Res<Time>,
+	theme: Res<Theme>,
+	scoreboard: Res<Scoreboard>,
+	mut displayed: ResMut<DisplayedScore>,
+	mut query: Query<&mut Text, With<ScoreboardText>>,
+) {
+	if displayed.target != scoreboard.score {
+		displayed.target = scoreboard.score;
+		// Scores only go down when a new run starts (or from the console), no point counting down
+		if (displayed.target as f32) < displayed.value {
+			displayed.value = displayed.target as f32;
+		}
+		displayed.rate = (displayed.target as f32 - displayed.value) / SCORE_CATCHUP_SECS;
+	}
+
+	let target = displayed.target as f32;
+	let climbing = displayed.value < target;
+	// Nothing to redraw once the count has caught up, unless the score was just reset
+	if !climbing && !scoreboard.is_changed() && !displayed.is_changed() {
+		return;
+	}
+	if climbing {
+		displayed.value = (displayed.value + displayed.rate * time.delta_seconds()).min(target);
+	}
+
+	let Ok(mut text) = query.get_single_mut() else {
+		return;
+	};
+	text.sections[0].value = format_thousands(displayed.value as usize);
+	text.sections[0].style.color = if climbing {
+		SCORE_CLIMBING_COLOUR
+	} else {
+		theme.scoreboard
+	};
+}
+
+// The game over screen should never show a score that's still counting up
+fn snap_displayed_score(scoreboard: Res<Scoreboard>, mut displayed: ResMut<DisplayedScore>) {
+	displayed.target = scoreboard.score;
+	displayed.value = scoreboard.score as f32;
+}
+
+// One of the three columns in the HUD bar, each takes a third of the width
+fn hud_slot(justify_content: JustifyContent) -> NodeBundle {
+	NodeBundle {
+		style: Style {
+			size: Size::width(Val::Percent(100.0 / 3.0)),
+			justify_content,
+			..default()
+		},
+		..default()
+	}
+}
+
+fn update_wave_text(wave: Res<Wave>, strings: Res<Strings>, mut query: Query<&mut Text, With<WaveText>>) {
+	for mut text in &mut query {
+		text.sections[0].value = strings.format("hud.wave", &[&wave.number.to_string()]);
+	}
+}
+
+fn update_currency_text(
+	currency: Res<Currency>,
+	strings: Res<Strings>,
+	mut query: Query<&mut Text, With<CurrencyText>>,
+) {
+	for mut text in &mut query {
+		text.sections[0].value = format!("  {}", strings.format("hud.scrap", &[&currency.0.to_string()]));
+	}
+}
+
+fn progress_track_colour(theme: &Theme) -> Color {
+	let mut colour = theme.scoreboard;
+	colour.set_a(WAVE_PROGRESS_TRACK_ALPHA);
+	colour
+}
+
+// Announce the best streak the recent kills add up to. A whole row dying at
+// once only announces its highest tier, and each tier only once until the
+// kills dry up.
+fn update_kill_streaks(
+	mut commands: Commands,
+	time: Res<Time>,
+	asset_server: Res<AssetServer>,
+	theme: Res<Theme>,
+	strings: Res<Strings>,
+	settings: Res<Settings>,
+	config: Res<GameConfig>,
+	mutators: Res<Mutators>,
+	assist: Res<AssistSettings>,
+	mut scoreboard: ResMut<Scoreboard>,
+	mut run_stats: ResMut<RunStats>,
+	mut killed_events: EventReader<EnemyKilledEvent>,
+	mut messages: EventWriter<GameMessage>,
+	mut sound_events: EventWriter<PlaySound>,
+) {
+	let now = time.elapsed_seconds();
+	let window = KillStreak::ALL
+		.iter()
+		.map(|streak| streak.rule(&config).1)
+		.fold(0.0, f32::max);
+
+	let killed = killed_events.iter().count();
+	run_stats.recent_kills.extend(std::iter::repeat(now).take(killed));
+	while run_stats.recent_kills.front().map_or(false, |&kill| now - kill > window) {
+		run_stats.recent_kills.pop_front();
+	}
+
+	if run_stats.recent_kills.is_empty() {
+		run_stats.streak = None;
+	}
+	if killed == 0 {
+		return;
+	}
+
+	let reached = KillStreak::ALL.into_iter().rev().find(|streak| {
+		let (kills, secs, _) = streak.rule(&config);
+		run_stats.recent_kills.iter().filter(|&&kill| now - kill <= secs).count() >= kills
+	});
+	let Some(streak) = reached.filter(|&streak| Some(streak) > run_stats.streak) else {
+		return;
+	};
+	run_stats.streak = Some(streak);
+
+	let bonus = streak.rule(&config).2;
+	award_points(&mut scoreboard, bonus, &mutators, &assist);
+
+	let name = strings.get(streak.name_key());
+	let bonus_label = format!("+{}", format_thousands(bonus));
+	messages.send(GameMessage(format!("{name} {bonus_label}")));
+
+	if settings.announcer {
+		let font = strings.font(&asset_server);
+		spawn_banner(&mut commands, &font, &theme, name, Some(&bonus_label));
+		sound_events.send(PlaySound::new(SoundId::Fanfare));
+	}
+}
+
+fn count_wave_kills(mut wave: ResMut<Wave>, mut killed_events: EventReader<EnemyKilledEvent>) {
+	let killed = killed_events.iter().count();
+	if killed > 0 {
+		wave.killed += killed;
+	}
+}
+
+// Ease the bar towards how much of the wave is dead. A new wave snaps it back
+// to empty rather than draining it.
+fn update_wave_progress(
+	time: Res<Time>,
+	theme: Res<Theme>,
+	wave: Res<Wave>,
+	strings: Res<Strings>,
+	mut shown: Local<f32>,
+	mut fill_query: Query<(&mut Style, &mut BackgroundColor), (With<WaveProgressFill>, Without<WaveProgressTrack>)>,
+	mut track_query: Query<(&Interaction, &mut BackgroundColor), With<WaveProgressTrack>>,
+	mut text_query: Query<&mut Text, With<WaveRemainingText>>,
+) {
+	let target = wave.progress();
+	if target < *shown {
+		*shown = target;
+	} else {
+		*shown += (target - *shown) * (1.0 - (-WAVE_PROGRESS_SMOOTHING * time.delta_seconds()).exp());
+	}
+
+	for (mut style, mut colour) in &mut fill_query {
+		style.size.width = Val::Percent(*shown * 100.0);
+		if theme.is_changed() {
+			*colour = theme.scoreboard.into();
+		}
+	}
+
+	let mut hovered = false;
+	for (interaction, mut colour) in &mut track_query {
+		hovered |= *interaction != Interaction::None;
+		if theme.is_changed() {
+			*colour = progress_track_colour(&theme).into();
+		}
+	}
+
+	for mut text in &mut text_query {
+		let label = if hovered {
+			strings.format("hud.remaining", &[&wave.remaining().to_string()])
+		} else {
+			String::new()
+		};
+
+		if text.sections[0].value != label {
+			text.sections[0].value = label;
+		}
+		if theme.is_changed() {
+			text.sections[0].style.color = theme.scoreboard;
+		}
+	}
+}
+
+fn update_high_score(
+	scoreboard: Res<Scoreboard>,
+	assist: Res<AssistSettings>,
+	active_challenge: Res<ActiveChallenge>,
+	mut high_score: ResMut<HighScore>,
+) {
+	if scoreboard.score > high_score.score {
+		high_score.score = scoreboard.score;
+		high_score.assisted = assist.enabled;
+		high_score.challenge = active_challenge.0.map(ChallengeCode::encode);
+	}
+}
+
+fn update_high_score_text(
+	high_score: Res<HighScore>,
+	strings: Res<Strings>,
+	mut query: Query<&mut Text, With<HighScoreText>>,
+) {
+	for mut text in &mut query {
+		text.sections[0].value = high_score.label(&strings);
+	}
+}
+
+// 1234567 -> "1,234,567"
+fn format_thousands(value: usize) -> String {
+	let digits = value.to_string();
+	let mut formatted = String::with_capacity(digits.len() + digits.len() / 3);
+
+	for (i, digit) in digits.chars().enumerate() {
+		if i > 0 && (digits.len() - i) % 3 == 0 {
+			formatted.push(',');
+		}
+		formatted.push(digit);
+	}
+
+	formatted
+}
+
+fn collision_check(
+	mut commands: Commands,
+	mut collision_events: EventWriter<CollisionEvent>,
+	mut hit_events: EventWriter<PlayerHitEvent>,
+	mut killed_events: EventWriter<EnemyKilledEvent>,
+	mut messages: EventWriter<GameMessage>,
+	mut scoreboard: ResMut<Scoreboard>,
+	mutators: Res<Mutators>,
+	assist: Res<AssistSettings>,
+	enemy_catalog: Res<EnemyCatalog>,
+	strings: Res<Strings>,
+	mut run_stats: ResMut<RunStats>,
+	mut currency: ResMut<Currency>,
+	bullet_query: Query<(Entity, &Transform, &Collider, &Faction, &SpawnId), With<Bullet>>,
+	enemy_query: Query<(Entity, &Transform, &Collider, &EnemyKind, &Faction, &SpawnId), With<Enemy>>,
+	player_query: Query<(&Transform, &Collider, &Faction), With<Player>>,
+	pickup_query: Query<(Entity, &Transform, &Collider, &Pickup, &SpawnId)>,
+) {
+	// Two bullets can hit the same enemy in one tick, it should only die once
+	let mut killed: Vec<Entity> = Vec::new();
+
+	// In spawn order rather than query order, so which bullet gets which enemy
+	// (and the order kills are rolled for drops) replays the same every time
+	let mut bullets: Vec<_> = bullet_query.iter().collect();
+	bullets.sort_by_key(|(_, _, _, _, spawn_id)| **spawn_id);
+	let mut enemies: Vec<_> = enemy_query.iter().collect();
+	enemies.sort_by_key(|(_, _, _, _, _, spawn_id)| **spawn_id);
+
+	let player = player_query.get_single().ok();
+
+	// A bullet that meets one that blocks it is used up, the other carries on
+	let mut blocked: Vec<Entity> = Vec::new();
+	for (i, &(bullet, transform, collider, &faction, _)) in bullets.iter().enumerate() {
+		for &(other, other_transform, other_collider, &other_faction, _) in &bullets[i + 1..] {
+			if blocked.contains(&bullet) || blocked.contains(&other) {
+				continue;
+			}
+			if !faction.blocks(other_faction) && !other_faction.blocks(faction) {
+				continue;
+			}
+
+			let collision = collide(
+				transform.translation,
+				collider.size(),
+				other_transform.translation,
+				other_collider.size(),
+			);
+
+			if collision.is_some() {
+				if faction.blocks(other_faction) {
+					commands.entity(other).despawn();
+					blocked.push(other);
+				}
+				if other_faction.blocks(faction) {
+					commands.entity(bullet).despawn();
+					blocked.push(bullet);
+				}
+			}
+		}
+	}
+
+	'bullets: for (bullet, bullet_transform, bullet_collider, &bullet_faction, _) in bullets {
+		if blocked.contains(&bullet) {
+			continue;
+		}
+
+		for &(enemy, enemy_transform, enemy_collider, &kind, &enemy_faction, _) in &enemies {
+			// Bullets pass straight through anything on their own side
+			if killed.contains(&enemy) || !bullet_faction.damages(enemy_faction) {
+				continue;
+			}
+
+			let collision = collide(
+				bullet_transform.translation,
+				bullet_collider.size(),
+				enemy_transform.translation,
+				enemy_collider.size(),
+			);
+
+			if collision.is_some() {
+				collision_events.send_default();
+				commands.entity(bullet).despawn();
+				kill_enemy(&mut commands, &mut killed_events, enemy, kind, enemy_transform.translation);
+
+				run_stats.shots_hit += 1;
+
+				let info = enemy_catalog.get(kind);
+				award_points(&mut scoreboard, info.points, &mutators, &assist);
+				messages.send(GameMessage(
+					strings.format("hud.enemy_destroyed", &[&info.name, &info.points.to_string()])
+				));
+				killed.push(enemy);
+				continue 'bullets;
+			}
+		}
+
+		let Some((player_transform, player_collider, &player_faction)) = player else {
+			continue;
+		};
+		if !bullet_faction.damages(player_faction) {
+			continue;
+		}
+
+		let collision = collide(
+			bullet_transform.translation,
+			bullet_collider.size(),
+			player_transform.translation,
+			player_collider.size(),
+		);
+
+		if collision.is_some() {
+			collision_events.send_default();
+			commands.entity(bullet).despawn();
+			hit_events.send_default();
+		}
+	}
+
+	// Pickups can't hurt the player, flying into one just collects it
+	if let Some((player_transform, player_collider, _)) = player {
+		let mut pickups: Vec<_> = pickup_query.iter().collect();
+		pickups.sort_by_key(|(_, _, _, _, spawn_id)| **spawn_id);
+
+		for (entity, transform, collider, pickup, _) in pickups {
+			let collision = collide(
+				player_transform.translation,
+				player_collider.size(),
+				transform.translation,
+				collider.size(),
+			);
+
+			if collision.is_some() {
+				currency.0 += pickup.value;
+				commands.entity(entity).despawn();
+			}
+		}
+	}
+}
+
+// One hit and the run is over, unless god mode is on
+fn end_run_on_hit(
+	god_mode: Res<GodMode>,
+	mut hit_events: EventReader<PlayerHitEvent>,
+	mut app_state: ResMut<NextState<AppState>>,
+) {
+	if hit_events.iter().count() > 0 && !god_mode.0 {
+		app_state.set(AppState::GameOver);
+	}
+}
+
+// Roll each dead enemy's drop table
+fn spawn_pickups(
+	mut commands: Commands,
+	enemy_catalog: Res<EnemyCatalog>,
+	orientation: Res<PlayfieldOrientation>,
+	mut rng: ResMut<GameRng>,
+	mut killed_events: EventReader<EnemyKilledEvent>,
+) {
+	for event in killed_events.iter() {
+		let drops = &enemy_catalog.get(event.kind).drops;
+
+		for (i, drop) in drops.iter().enumerate() {
+			if rng.next_f32() >= drop.chance {
+				continue;
+			}
+
+			// Bigger scrap is a bigger square, and spread out so they don't stack
+			let size = Vec2::splat(6.0 + drop.value.min(10) as f32 * 0.5);
+			let position = event.position + Vec3::new((i as f32 - 1.0) * 12.0, 0.0, 0.0);
+
+			commands.spawn((
+				SpriteBundle {
+					sprite: Sprite {
+						color: PICKUP_COLOUR,
+						custom_size: Some(size),
+						..default()
+					},
+					transform: Transform::from_translation(position),
+					..default()
+				},
+				Pickup {
+					value: drop.value,
+					timer: Timer::from_seconds(PICKUP_SECS, TimerMode::Once),
+				},
+				Collider { half_extents: size / 2.0 },
+				Velocity(-orientation.up * PICKUP_FALL_SPEED),
+				PreviousTransform(position),
+				GameEntity,
+			));
+		}
+	}
+}
+
+// Pull nearby pickups in once the player has a magnet
+fn attract_pickups(
+	config: Res<GameConfig>,
+	orientation: Res<PlayfieldOrientation>,
+	player_query: Query<&Transform, (With<Player>, Without<Pickup>)>,
+	mut pickup_query: Query<(&Transform, &mut Velocity), With<Pickup>>,
+) {
+	let Ok(player_transform) = player_query.get_single() else {
+		return;
+	};
+
+	for (transform, mut velocity) in &mut pickup_query {
+		let offset = (player_transform.translation - transform.translation).truncate();
+
+		velocity.0 = if offset.length() < config.pickup_magnet_radius {
+			offset.normalize_or_zero() * PICKUP_MAGNET_SPEED
+		} else {
+			-orientation.up * PICKUP_FALL_SPEED
+		};
+	}
+}
+
+// Time pickups out, blinking for the last PICKUP_BLINK_SECS
+fn update_pickups(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut query: Query<(Entity, &mut Pickup, &mut Visibility)>,
+) {
+	for (entity, mut pickup, mut visibility) in &mut query {
+		if pickup.timer.tick(time.delta()).finished() {
+			commands.entity(entity).despawn();
+			continue;
+		}
+
+		let left = pickup.timer.duration().as_secs_f32() - pickup.timer.elapsed_secs();
+		let hidden = left < PICKUP_BLINK_SECS && (left * PICKUP_BLINKS_PER_SEC) as u32 % 2 == 0;
+		*visibility = if hidden { Visibility::Hidden } else { Visibility::Inherited };
+	}
+}
+
+// The inspector can despawn the player or remove the scoreboard mid-run. The
+// systems that need them skip themselves when they're gone, this just says why
+// nothing is happening (once, not every frame).
+fn warn_missing_game_state(
+	scoreboard: Option<Res<Scoreboard>>,
+	player_query: Query<(), With<Player>>,
+	mut warned: Local<(bool, bool)>,
+) {
+	let (warned_scoreboard, warned_player) = &mut *warned;
+
+	if scoreboard.is_none() && !*warned_scoreboard {
+		warn!("The Scoreboard resource is missing, scoring is paused");
+	}
+	*warned_scoreboard = scoreboard.is_none();
+
+	if player_query.is_empty() && !*warned_player {
+		warn!("There's no player, movement and shooting are paused");
+	}
+	*warned_player = player_query.is_empty();
+}
+
+fn update_sound_manager(time: Res<Time>, config: Res<GameConfig>, mut sound_manager: ResMut<SoundManager>) {
+	sound_manager.playing.retain_mut(|(_, timer)| !timer.tick(time.delta()).finished());
+	sound_manager.update_duck(time.delta_seconds(), &config);
+}
+
+// Play everything asked for this frame, within SoundManager's limits
+fn audio_playback(
+	mut play_events: EventReader<PlaySound>,
+	audio: Res<Audio>,
+	shooting_sound: Res<ShootingSound>,
+	fanfare: Res<FanfareSound>,
+	ui_sounds: Res<UiSounds>,
+	enemy_catalog: Res<EnemyCatalog>,
+	settings: Res<Settings>,
+	mut sound_manager: ResMut<SoundManager>,
+	// Not the run's GameRng, or how sounds vary would change the gameplay rolls
+	// and challenge codes wouldn't replay the same
+	mut rng: Local<GameRng>,
+) {
+	for event in play_events.iter() {
+		let source = match event.sound {
+			SoundId::Shoot => &shooting_sound.0,
+			SoundId::EnemyDeath(kind) => &enemy_catalog.get(kind).death_sound_handle,
+			SoundId::UiHover => &ui_sounds.hover,
+			SoundId::UiClick => &ui_sounds.click,
+			SoundId::Fanfare => &fanfare.0,
+		};
+
+		let speed = 1.0 + (rng.next_f32() * 2.0 - 1.0) * event.pitch_variance;
+		let playback = PlaybackSettings::ONCE.with_volume(event.volume).with_speed(speed);
+		let played = sound_manager.play(event.sound.effect(), &audio, source, playback);
+
+		if played && event.sound.ducks_music() && settings.music_ducking {
+			sound_manager.duck_music();
+		}
+	}
+}
+
+fn start_music(
+	mut commands: Commands,
+	audio: Res<Audio>,
+	audio_sinks: Res<Assets<AudioSink>>,
+	music: Res<MusicAssets>,
+) {
+	let base = audio.play_with_settings(music.base.clone(), PlaybackSettings::LOOP.with_volume(MUSIC_VOLUME));
+	let tension = audio.play_with_settings(music.tension.clone(), PlaybackSettings::LOOP.with_volume(0.0));
+
+	// Audio::play only hands back weak handles, keep strong ones so the sinks
+	// stick around for us to change the volume
+	commands.insert_resource(MusicLayers {
+		base: audio_sinks.get_handle(base),
+		tension: audio_sinks.get_handle(tension),
+		tension_volume: 0.0,
+	});
+}
+
+fn stop_music(
+	mut commands: Commands,
+	audio_sinks: Res<Assets<AudioSink>>,
+	layers: Option<Res<MusicLayers>>,
+) {
+	let Some(layers) = layers else {
+		return;
+	};
+
+	for sink in [&layers.base, &layers.tension] {
+		if let Some(sink) = audio_sinks.get(sink) {
+			sink.stop();
+		}
+	}
+
+	commands.remove_resource::<MusicLayers>();
+}
+
+fn update_music_tension(
+	time: Res<Time>,
+	config: Res<GameConfig>,
+	sound_manager: Res<SoundManager>,
+	audio_sinks: Res<Assets<AudioSink>>,
+	mut layers: ResMut<MusicLayers>,
+	orientation: Res<PlayfieldOrientation>,
+	enemy_query: Query<&Transform, With<Enemy>>,
+	player_query: Query<&Transform, With<Player>>,
+) {
+	let Ok(player_transform) = player_query.get_single() else {
+		return;
+	};
+
+	let lowest_enemy_height = enemy_query
+		.iter()
+		.map(|transform| orientation.height_of(transform.translation))
+		.reduce(f32::min);
+
+	let target = formation_tension(lowest_enemy_height, orientation.height_of(player_transform.translation));
+	let blend = (MUSIC_TENSION_SMOOTHING * time.delta_seconds()).min(1.0);
+	layers.tension_volume += (target - layers.tension_volume) * blend;
+
+	// Ducking scales both layers on top of everything else
+	let volume = MUSIC_VOLUME * sound_manager.music_volume(&config);
+	if let Some(sink) = audio_sinks.get(&layers.base) {
+		sink.set_volume(volume);
+	}
+	if let Some(sink) = audio_sinks.get(&layers.tension) {
+		sink.set_volume(volume * layers.tension_volume);
+	}
+}
+
+// 0 while the formation is where it spawned, up to 1 as its lowest enemy
+// reaches the player. Heights are along the playfield's up direction.
+fn formation_tension(lowest_enemy_height: Option<f32>, player_height: f32) -> f32 {
+	let Some(lowest_enemy_height) = lowest_enemy_height else {
+		return 0.0;
+	};
+
+	let start_height = ENEMY_GRID_TOP - (ENEMY_ROWS - 1) as f32 * ENEMY_SPACING.y;
+	let distance = start_height - player_height;
+
+	((start_height - lowest_enemy_height) / distance).clamp(0.0, 1.0)
+}
+
+fn play_hit_sound(
+	mut killed_events: EventReader<EnemyKilledEvent>,
+	mut sound_events: EventWriter<PlaySound>,
+) {
+	for event in killed_events.iter() {
+		let sound = PlaySound::new(SoundId::EnemyDeath(event.kind));
+		sound_events.send(sound.with_pitch_variance(ENEMY_DEATH_PITCH_VARIANCE));
+	}
+}
+
+fn remove_offscreen_entities(
+	mut commands: Commands,
+	mut run_stats: ResMut<RunStats>,
+	query: Query<(Entity, &Transform, Option<&Ricochet>), With<Bullet>>,
+) {
+	// Bullets can leave through any side once gravity and ricochets are involved
+	for (entity, transform, ricochet) in query.iter() {
+		let position = transform.translation;
+		// The sides are walls for bullets that still have a bounce in them
+		let out_of_sides = position.x.abs() > WIDTH / 2.0 && ricochet.is_none();
+
+		if position.y.abs() > HEIGHT / 2.0 || out_of_sides {
+			commands.entity(entity).despawn();
+			run_stats.shots_missed += 1;
+		}
+	}
+}
+
+fn toggle_debug_overlay(
+	keyboard_input: Res<Input<KeyCode>>,
+	mut query: Query<&mut Visibility, With<DebugOverlay>>,
+) {
+	if !keyboard_input.just_pressed(KeyCode::F3) {
+		return;
+	}
+
+	for mut visibility in &mut query {
+		*visibility = match *visibility {
+			Visibility::Hidden => Visibility::Visible,
+			_ => Visibility::Hidden,
+		};
+	}
+}
+
+fn update_debug_overlay(
+	time: Res<Time>,
+	mut timer: ResMut<DebugOverlayTimer>,
+	diagnostics: Res<Diagnostics>,
+	app_state: Res<State<AppState>>,
+	audio_formats: Res<AudioFormats>,
+	bullet_query: Query<(), With<Bullet>>,
+	enemy_query: Query<(), With<Enemy>>,
+	mut query: Query<(&mut Text, &Visibility), With<DebugOverlay>>,
+) {
+	if !timer.tick(time.delta()).just_finished() {
+		return;
+	}
+
+	let Ok((mut text, visibility)) = query.get_single_mut() else {
+		return;
+	};
+
+	// No point building the string if nobody can see it
+	if *visibility == Visibility::Hidden {
+		return;
+	}
+
+	let fps = diagnostics
+		.get(FrameTimeDiagnosticsPlugin::FPS)
+		.and_then(|fps| fps.smoothed())
+		.unwrap_or(0.0);
+
+	// Min/avg/max over the frame time history (in milliseconds)
+	let (min_frame, avg_frame, max_frame) = diagnostics
+		.get(FrameTimeDiagnosticsPlugin::FRAME_TIME)
+		.map(|frame_time| {
+			let min = frame_time.values().copied().fold(f64::INFINITY, f64::min);
+			let max = frame_time.values().copied().fold(0.0, f64::max);
+			let avg = frame_time.average().unwrap_or(0.0);
+
+			(if min.is_finite() { min } else { 0.0 }, avg, max)
+		})
+		.unwrap_or((0.0, 0.0, 0.0));
+
+	let entity_count = diagnostics
+		.get(EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+		.and_then(|count| count.value())
+		.unwrap_or(0.0);
+
+	text.sections[0].value = format!(
+		"FPS      {:>6.1}\n\
+		 Frame ms {:>5.2} / {:>5.2} / {:>5.2}\n\
+		 Entities {:>6}\n\
+		 Bullets  {:>6}\n\
+		 Enemies  {:>6}\n\
+		 State    {:?}\n\
+		 Audio    {}",
+		fps,
+		min_frame,
+		avg_frame,
+		max_frame,
+		entity_count as usize,
+		bullet_query.iter().count(),
+		enemy_query.iter().count(),
+		app_state.0,
+		audio_formats.summary(),
+	);
+}
+
+/// The cheat console, god mode, debug hotkeys, chaos test and `--bench`.
+/// Added by [`GamePlugin`] when the `devtools` feature is on.
+#[cfg(feature = "devtools")]
+pub struct DevToolsPlugin;
+
+#[cfg(feature = "devtools")]
+impl Plugin for DevToolsPlugin {
+	fn build(&self, app: &mut App) {
+		app
+			.init_resource::<DevConsole>()
+			.insert_resource(ConsoleCommands::default()
+				.with("help", false, console_help)
+				.with("score", true, console_score)
+				.with("killall", true, console_killall)
+				.with("wave", true, console_wave)
+				.with("seed", false, console_seed)
+				.with("god", true, console_god)
+				.with("chaos", true, console_chaos)
+				.with("slow", true, console_slow)
+				.with("assets", false, console_assets)
+			)
+			.insert_resource(AssetWatch {
+				timer: Timer::from_seconds(ASSET_WATCH_INTERVAL_SECS, TimerMode::Repeating),
+				samples: 0,
+				history: VecDeque::new(),
+			})
+			.add_system(watch_asset_counts)
+			.add_startup_system(console_setup)
+			.add_startup_system(god_label_setup)
+			.add_system(debug_input.in_set(OnUpdate(AppState::GameRunning)))
+			.add_system(update_god_label)
+			.add_system(
+				chaos_test
+					.run_if(resource_exists::<ChaosTest>())
+					.in_set(OnUpdate(AppState::GameRunning))
+			)
+			// Before the fixed tick, so move_player doesn't see keys typed into the console
+			.add_system(
+				console_input
+					.in_base_set(CoreSet::PreUpdate)
+					.after(bevy::input::InputSystem)
+			)
+			.add_system(run_console_commands.after(console_input))
+			.add_system(update_console_text.after(run_console_commands));
+
+		if std::env::args().any(|arg| arg == "--bench") {
+			app
+				.add_startup_system(start_bench)
+				.add_system(bench_setup.after(game_setup).in_schedule(OnEnter(AppState::GameRunning)))
+				.add_system(run_bench.in_set(OnUpdate(AppState::GameRunning)));
+		}
+	}
+}
+
+#[cfg(feature = "devtools")]
+#[derive(Component)]
+struct ConsoleUi;
+
+#[cfg(feature = "devtools")]
+#[derive(Component)]
+struct ConsoleText;
+
+#[cfg(feature = "devtools")]
+#[derive(Component)]
+struct GodLabel;
+
+#[cfg(feature = "devtools")]
+#[derive(Resource)]
+struct ChaosTest {
+	duration: Timer,
+	interval: Timer,
+	rng: u64,
+}
+
+// How many of each kind of asset are loaded. Everything spawned during play is
+// meant to share handles from resources like BulletAssets, so once the game's
+// warmed up these should stay flat.
+#[cfg(feature = "devtools")]
+#[derive(Clone, Copy, PartialEq)]
+struct AssetCounts {
+	audio: usize,
+	meshes: usize,
+	materials: usize,
+	images: usize,
+}
+
+#[cfg(feature = "devtools")]
+impl AssetCounts {
+	fn from_world(world: &World) -> Self {
+		AssetCounts {
+			audio: world.resource::<Assets<AudioSource>>().len(),
+			meshes: world.resource::<Assets<Mesh>>().len(),
+			materials: world.resource::<Assets<ColorMaterial>>().len(),
+			images: world.resource::<Assets<Image>>().len(),
+		}
+	}
+
+	fn fields(&self) -> [(&'static str, usize); 4] {
+		[
+			("audio", self.audio),
+			("meshes", self.meshes),
+			("materials", self.materials),
+			("images", self.images),
+		]
+	}
+}
+
+#[cfg(feature = "devtools")]
+impl std::fmt::Display for AssetCounts {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		let fields: Vec<String> = self.fields().iter().map(|(name, count)| format!("{name} {count}")).collect();
+		write!(f, "{}", fields.join(", "))
+	}
+}
+
+#[cfg(feature = "devtools")]
+#[derive(Resource)]
+struct AssetWatch {
+	timer: Timer,
+	// Taken since startup, for skipping the warm-up
+	samples: usize,
+	// The last few, oldest first
+	history: VecDeque<AssetCounts>,
+}
+
+#[cfg(feature = "devtools")]
+#[derive(Resource, Default)]
+struct DevConsole {
+	open: bool,
+	input: String,
+	// Lines entered this frame, waiting for run_console_commands to get world access
+	submitted: Vec<String>,
+	scrollback: VecDeque<String>,
+}
+
+#[cfg(feature = "devtools")]
+impl DevConsole {
+	fn print(&mut self, line: impl Into<String>) {
+		self.scrollback.push_back(line.into());
+
+		while self.scrollback.len() > CONSOLE_SCROLLBACK_LINES {
+			self.scrollback.pop_front();
+		}
+	}
+}
+
+// A console command gets the whole world so it can touch any resource or spawn
+// anything it likes. The returned string (or error) is printed to the scrollback.
+#[cfg(feature = "devtools")]
+type ConsoleCommandFn = fn(&mut World, &[&str]) -> Result<String, String>;
+
+#[cfg(feature = "devtools")]
+#[derive(Clone, Copy)]
+struct ConsoleCommand {
+	run: ConsoleCommandFn,
+	// Whether using this command taints the run
+	cheat: bool,
+}
+
+#[cfg(feature = "devtools")]
+#[derive(Resource, Default)]
+struct ConsoleCommands(HashMap<&'static str, ConsoleCommand>);
+
+#[cfg(feature = "devtools")]
+impl ConsoleCommands {
+	fn with(mut self, name: &'static str, cheat: bool, run: ConsoleCommandFn) -> Self {
+		self.0.insert(name, ConsoleCommand { run, cheat });
+		self
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn console_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	let font = asset_server.load("fonts/dejavu/DejaVuSansMono.ttf");
+
+	commands.spawn((
+		NodeBundle {
+			style: Style {
+				position_type: PositionType::Absolute,
+				position: UiRect {
+					top: Val::Px(0.0),
+					left: Val::Px(0.0),
+					..default()
+				},
+				size: Size::width(Val::Percent(100.0)),
+				padding: UiRect::all(Val::Px(5.0)),
+				..default()
+			},
+			background_color: CONSOLE_BG_COLOUR.into(),
+			visibility: Visibility::Hidden,
+			z_index: ZIndex::Global(i32::MAX - 1),
+			..default()
+		},
+		ConsoleUi,
+	))
+	.with_children(|parent| {
+		parent.spawn((
+			TextBundle::from_sections([
+				TextSection::from_style(TextStyle {
+					font: font.clone(),
+					font_size: CONSOLE_FONT_SIZE,
+					color: CONSOLE_SCROLLBACK_COLOUR,
+				}),
+				TextSection::new(
+					"> _",
+					TextStyle {
+						font,
+						font_size: CONSOLE_FONT_SIZE,
+						color: CONSOLE_INPUT_COLOUR,
+					},
+				),
+			]),
+			ConsoleText,
+		));
+	});
+}
+
+#[cfg(feature = "devtools")]
+fn console_input(
+	mut keyboard_input: ResMut<Input<KeyCode>>,
+	mut characters: EventReader<ReceivedCharacter>,
+	mut console: ResMut<DevConsole>,
+	mut query: Query<&mut Visibility, With<ConsoleUi>>,
+) {
+	if keyboard_input.just_pressed(KeyCode::Grave) {
+		console.open = !console.open;
+
+		for mut visibility in &mut query {
+			*visibility = if console.open { Visibility::Visible } else { Visibility::Hidden };
+		}
+	}
+
+	if !console.open {
+		characters.clear();
+		return;
+	}
+
+	for character in characters.iter() {
+		// The backtick that opened the console shouldn't end up in the input
+		if character.char != '`' && !character.char.is_control() {
+			console.input.push(character.char);
+		}
+	}
+
+	if keyboard_input.just_pressed(KeyCode::Back) {
+		console.input.pop();
+	}
+
+	if keyboard_input.just_pressed(KeyCode::Return) {
+		let line = std::mem::take(&mut console.input);
+		console.print(format!("> {line}"));
+		console.submitted.push(line);
+	}
+
+	// Swallow the keyboard while typing, otherwise "score 5000" also fires a bullet
+	keyboard_input.reset_all();
+}
+
+#[cfg(feature = "devtools")]
+fn run_console_commands(world: &mut World) {
+	let submitted = std::mem::take(&mut world.resource_mut::<DevConsole>().submitted);
+
+	for line in submitted {
+		let mut words = line.split_whitespace();
+		let Some(name) = words.next() else {
+			continue;
+		};
+		let args: Vec<&str> = words.collect();
+
+		let command = world.resource::<ConsoleCommands>().0.get(name).copied();
+		let output = match command {
+			Some(command) => {
+				let output = (command.run)(world, &args);
+
+				if command.cheat && output.is_ok() {
+					world.resource_mut::<TaintedRun>().0 = true;
+				}
+
+				output
+			},
+			None => Err(format!("unknown command '{name}', try 'help'")),
+		};
+
+		let mut console = world.resource_mut::<DevConsole>();
+		match output {
+			Ok(message) => console.print(message),
+			Err(message) => console.print(format!("error: {message}")),
+		}
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn update_console_text(
+	console: Res<DevConsole>,
+	mut query: Query<&mut Text, With<ConsoleText>>,
+) {
+	if !console.is_changed() {
+		return;
+	}
+
+	for mut text in &mut query {
+		text.sections[0].value = console.scrollback
+			.iter()
+			.map(|line| format!("{line}\n"))
+			.collect();
+		text.sections[1].value = format!("> {}_", console.input);
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn console_help(world: &mut World, _args: &[&str]) -> Result<String, String> {
+	let mut names: Vec<&str> = world.resource::<ConsoleCommands>().0.keys().copied().collect();
+	names.sort_unstable();
+
+	Ok(format!("commands: {}", names.join(", ")))
+}
+
+#[cfg(feature = "devtools")]
+fn console_score(world: &mut World, args: &[&str]) -> Result<String, String> {
+	let score = args
+		.first()
+		.and_then(|arg| arg.parse().ok())
+		.ok_or("usage: score <points>")?;
+
+	let Some(mut scoreboard) = world.get_resource_mut::<Scoreboard>() else {
+		return Err("there's no scoreboard".to_string());
+	};
+	scoreboard.score = score;
+
+	Ok(format!("score set to {score}"))
+}
+
+#[cfg(feature = "devtools")]
+fn console_killall(world: &mut World, _args: &[&str]) -> Result<String, String> {
+	let mut system_state: SystemState<(
+		Commands,
+		EventWriter<EnemyKilledEvent>,
+		Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+	)> = SystemState::new(world);
+
+	let killed = {
+		let (mut commands, mut killed_events, query) = system_state.get_mut(world);
+		kill_all_enemies(&mut commands, &mut killed_events, &query)
+	};
+	system_state.apply(world);
+
+	Ok(format!("killed {killed} enemies"))
+}
+
+#[cfg(feature = "devtools")]
+fn console_wave(world: &mut World, args: &[&str]) -> Result<String, String> {
+	let to: u32 = args
+		.first()
+		.and_then(|arg| arg.parse().ok())
+		.filter(|&to| to >= 1)
+		.ok_or("usage: wave <number>")?;
+	if world.resource::<State<AppState>>().0 != AppState::GameRunning {
+		return Err("only during a run".to_string());
+	}
+
+	let mut system_state: SystemState<(
+		Commands,
+		EventWriter<EnemyKilledEvent>,
+		ResMut<Wave>,
+		ResMut<WaveTally>,
+		Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+	)> = SystemState::new(world);
+
+	{
+		let (mut commands, mut killed_events, mut wave, mut tally, query) = system_state.get_mut(world);
+		skip_to_wave(&mut commands, &mut killed_events, &query, &mut wave, &mut tally, to);
+	}
+	system_state.apply(world);
+
+	Ok(format!("skipping to wave {to}"))
+}
+
+// On its own, shows what this run was seeded with. Given a number, reseeds the
+// game's randomness from it, which counts as a cheat.
+#[cfg(feature = "devtools")]
+fn console_seed(world: &mut World, args: &[&str]) -> Result<String, String> {
+	let Some(arg) = args.first() else {
+		return match world.resource::<RunCode>().0 {
+			Some(code) => Ok(format!("seed {}, challenge code {}", code.seed, code.encode())),
+			None => Err("no run has started yet".to_string()),
+		};
+	};
+
+	let seed: u32 = arg.parse().map_err(|_| "usage: seed [number]")?;
+	*world.resource_mut::<GameRng>() = GameRng::from_seed(seed);
+	world.resource_mut::<TaintedRun>().0 = true;
+
+	Ok(format!("reseeded with {seed}"))
+}
+
+#[cfg(feature = "devtools")]
+fn console_god(world: &mut World, _args: &[&str]) -> Result<String, String> {
+	let mut god_mode = world.resource_mut::<GodMode>();
+	god_mode.0 = !god_mode.0;
+
+	Ok(format!("god mode {}", if god_mode.0 { "on" } else { "off" }))
+}
+
+// Pushes a formation speed modifier that eases back to normal
+#[cfg(feature = "devtools")]
+fn console_slow(world: &mut World, args: &[&str]) -> Result<String, String> {
+	let usage = "usage: slow <multiplier> <seconds>";
+	// "nan" and "inf" parse too, but would panic in the timer or break the march
+	let multiplier: f32 = args
+		.first()
+		.and_then(|arg| arg.parse().ok())
+		.filter(|multiplier: &f32| multiplier.is_finite())
+		.ok_or(usage)?;
+	let secs: f32 = args
+		.get(1)
+		.and_then(|arg| arg.parse().ok())
+		.filter(|secs: &f32| secs.is_finite())
+		.ok_or(usage)?;
+	if multiplier < 0.0 || secs <= 0.0 {
+		return Err(usage.to_string());
+	}
+	if secs > CONSOLE_SLOW_MAX_SECS {
+		return Err(format!("at most {CONSOLE_SLOW_MAX_SECS}s"));
+	}
+
+	world.resource_mut::<FormationModifiers>().push(multiplier, secs, true);
+
+	Ok(format!("formation at x{multiplier} for {secs}s"))
+}
+
+#[cfg(feature = "devtools")]
+fn console_chaos(world: &mut World, _args: &[&str]) -> Result<String, String> {
+	if world.remove_resource::<ChaosTest>().is_some() {
+		return Ok("chaos test stopped".to_string());
+	}
+
+	// Doesn't need to be good randomness, just different each time
+	let seed = std::time::SystemTime::now()
+		.duration_since(std::time::UNIX_EPOCH)
+		.map_or(1, |duration| duration.as_nanos() as u64)
+		| 1;
+	world.insert_resource(ChaosTest {
+		duration: Timer::from_seconds(CHAOS_TEST_SECS, TimerMode::Once),
+		interval: Timer::from_seconds(CHAOS_TEST_INTERVAL_SECS, TimerMode::Repeating),
+		rng: seed,
+	});
+
+	Ok(format!("chaos test running for {CHAOS_TEST_SECS}s"))
+}
+
+// Despawns a random player, enemy or bullet every so often, to check the game
+// carries on without things it expects to be there
+#[cfg(feature = "devtools")]
+fn chaos_test(
+	mut commands: Commands,
+	time: Res<Time>,
+	mut chaos: ResMut<ChaosTest>,
+	query: Query<Entity, Or<(With<Player>, With<Enemy>, With<Bullet>)>>,
+) {
+	chaos.duration.tick(time.delta());
+	if chaos.duration.finished() {
+		info!("Chaos test finished");
+		commands.remove_resource::<ChaosTest>();
+		return;
+	}
+
+	if !chaos.interval.tick(time.delta()).just_finished() {
+		return;
+	}
+
+	let entities: Vec<Entity> = query.iter().collect();
+	if entities.is_empty() {
+		return;
+	}
+
+	// xorshift
+	chaos.rng ^= chaos.rng << 13;
+	chaos.rng ^= chaos.rng >> 7;
+	chaos.rng ^= chaos.rng << 17;
+	let entity = entities[(chaos.rng % entities.len() as u64) as usize];
+
+	info!("Chaos test despawning {entity:?}");
+	commands.entity(entity).despawn_recursive();
+}
+
+// Logs the asset counts every so often and warns about any collection that
+// only ever goes up, so a long session (or a chaos test left running) shows
+// up leaks
+#[cfg(feature = "devtools")]
+fn watch_asset_counts(world: &mut World) {
+	let delta = world.resource::<Time>().delta();
+	if !world.resource_mut::<AssetWatch>().timer.tick(delta).just_finished() {
+		return;
+	}
+
+	let counts = AssetCounts::from_world(world);
+	debug!("Assets: {counts}");
+
+	let mut watch = world.resource_mut::<AssetWatch>();
+	watch.samples += 1;
+	if watch.samples <= ASSET_WATCH_WARMUP_SAMPLES {
+		return;
+	}
+
+	watch.history.push_back(counts);
+	if watch.history.len() <= ASSET_WATCH_GROWTH_SAMPLES {
+		return;
+	}
+	watch.history.pop_front();
+
+	let mut leaking = false;
+	for (i, (name, _)) in counts.fields().iter().enumerate() {
+		let growing = watch
+			.history
+			.iter()
+			.zip(watch.history.iter().skip(1))
+			.all(|(before, after)| before.fields()[i].1 < after.fields()[i].1);
+
+		if growing {
+			warn!(
+				"{name} assets have gone up at every check for {}s ({} -> {}), something may be leaking them",
+				ASSET_WATCH_GROWTH_SAMPLES as f32 * ASSET_WATCH_INTERVAL_SECS,
+				watch.history[0].fields()[i].1,
+				counts.fields()[i].1,
+			);
+			leaking = true;
+		}
+	}
+
+	// Start over, rather than warning again every interval
+	if leaking {
+		watch.history.clear();
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn console_assets(world: &mut World, _args: &[&str]) -> Result<String, String> {
+	let counts = AssetCounts::from_world(world);
+
+	match world.resource::<AssetWatch>().history.front() {
+		Some(oldest) => Ok(format!("{counts} (was {oldest})")),
+		None => Ok(counts.to_string()),
+	}
+}
+
+// Skip the menu and go straight into the stress scene
+#[cfg(feature = "devtools")]
+fn start_bench(mut app_state: ResMut<NextState<AppState>>) {
+	info!("Running the benchmark, {BENCH_TICKS} ticks");
+	app_state.set(AppState::GameRunning);
+}
+
+// Always the same scene, so runs can be compared: the first wave's formation
+// BENCH_WAVES times over, and a grid of bullets coming up through it
+#[cfg(feature = "devtools")]
+fn bench_setup(
+	mut commands: Commands,
+	theme: Res<Theme>,
+	settings: Res<Settings>,
+	config: Res<GameConfig>,
+	enemy_catalog: Res<EnemyCatalog>,
+	bullet_assets: Res<BulletAssets>,
+) {
+	let orientation = PlayfieldOrientation::default();
+	// Always the full grid, so results compare between formation files
+	let layout = FormationLayout::classic();
+
+	// game_setup has already spawned one
+	for wave in 1..BENCH_WAVES {
+		let mut formation = FormationParams::for_wave(1, &config);
+		formation.start_drop = wave as f32 * ENEMY_SPACING.y / BENCH_WAVES as f32;
+		spawn_formation(&mut commands, &theme, &enemy_catalog, &orientation, &mut formation, &layout, false);
+	}
+
+	let columns = 50;
+	for i in 0..BENCH_BULLETS {
+		let x = -(WIDTH / 2.0) + (i % columns) as f32 * WIDTH / columns as f32;
+		let y = -(HEIGHT / 2.0) + (i / columns) as f32 * BULLET_SIZE.y * 2.0;
+		let position = Vec3::new(x, y, 0.0);
+
+		commands.spawn((
+			SpriteBundle {
+				texture: bullet_assets.sprite.clone(),
+				sprite: Sprite {
+					color: Faction::Player.bullet_colour(&theme, &settings),
+					custom_size: Some(BULLET_SIZE),
+					..default()
+				},
+				transform: Transform::from_translation(position),
+				..default()
+			},
+			Bullet,
+			Faction::Player,
+			GameEntity,
+			Collider { half_extents: BULLET_SIZE / 2.0 },
+			Velocity(Vec2::Y * BULLET_SPEED),
+			PreviousTransform(position),
+		));
+	}
+}
+
+// Runs the fixed tick BENCH_TICKS times back to back, times each one, prints
+// the results as JSON and quits. Per-system timings would need tracing, so this
+// is the whole tick (movement, collisions, kills and wave spawning together).
+#[cfg(feature = "devtools")]
+fn run_bench(world: &mut World, mut done: Local<bool>) {
+	// AppExit only takes effect at the end of the frame
+	if *done {
+		return;
+	}
+	*done = true;
+
+	let enemies = world.query_filtered::<(), With<Enemy>>().iter(world).count();
+	let bullets = world.query_filtered::<(), With<Bullet>>().iter(world).count();
+
+	let mut ticks: Vec<f64> = Vec::with_capacity(BENCH_TICKS);
+	for _ in 0..BENCH_TICKS {
+		let start = std::time::Instant::now();
+		world.run_schedule(CoreSchedule::FixedUpdate);
+		ticks.push(start.elapsed().as_secs_f64() * 1000.0);
+	}
+
+	let average = ticks.iter().sum::<f64>() / ticks.len() as f64;
+	ticks.sort_by(|a, b| a.total_cmp(b));
+	let p95 = ticks[(ticks.len() * 95 / 100).min(ticks.len() - 1)];
+	let max = ticks[ticks.len() - 1];
+
+	println!(
+		"{{\"ticks\":{BENCH_TICKS},\"enemies\":{enemies},\"bullets\":{bullets},\"tick_ms\":{{\"average\":{average:.4},\"p95\":{p95:.4},\"max\":{max:.4}}}}}"
+	);
+
+	world.send_event(AppExit);
+}
+
+// Clears the wave through the normal kill path, so next_wave brings in wave
+// `to`. The tally is skipped too, unless it's already being shown.
+#[cfg(feature = "devtools")]
+fn skip_to_wave(
+	commands: &mut Commands,
+	killed_events: &mut EventWriter<EnemyKilledEvent>,
+	query: &Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+	wave: &mut Wave,
+	tally: &mut WaveTally,
+	to: u32,
+) {
+	kill_all_enemies(commands, killed_events, query);
+	wave.number = to - 1;
+	if tally.timer.is_none() {
+		tally.finished = true;
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn kill_all_enemies(
+	commands: &mut Commands,
+	killed_events: &mut EventWriter<EnemyKilledEvent>,
+	query: &Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+) -> usize {
+	let mut killed = 0;
+
+	for (entity, transform, &kind) in query.iter() {
+		kill_enemy(commands, killed_events, entity, kind, transform.translation);
+		killed += 1;
+	}
+
+	killed
+}
+
+#[cfg(feature = "devtools")]
+fn god_label_setup(
+	mut commands: Commands,
+	asset_server: Res<AssetServer>,
+) {
+	let mut god_label = TextBundle::from_section(
+		"GOD",
+		TextStyle {
+			font: asset_server.load("fonts/amiga4ever/amiga4ever.ttf"),
+			font_size: SCOREBOARD_FONT_SIZE,
+			color: GOD_LABEL_COLOUR,
+		},
+	)
+	.with_style(Style {
+		position_type: PositionType::Absolute,
+		position: UiRect {
+			bottom: Val::Px(5.0),
+			left: Val::Px(5.0),
+			..default()
+		},
+		..default()
+	});
+	god_label.visibility = Visibility::Hidden;
+
+	commands.spawn((god_label, GodLabel));
+}
+
+// G: toggle god mode, K: kill every enemy on screen, N: skip to the next wave.
+// U (force a UFO) waits on there being a UFO to spawn.
+#[cfg(feature = "devtools")]
+fn debug_input(
+	keyboard_input: Res<Input<KeyCode>>,
+	mut commands: Commands,
+	mut god_mode: ResMut<GodMode>,
+	mut tainted: ResMut<TaintedRun>,
+	mut wave: ResMut<Wave>,
+	mut tally: ResMut<WaveTally>,
+	mut killed_events: EventWriter<EnemyKilledEvent>,
+	enemy_query: Query<(Entity, &Transform, &EnemyKind), With<Enemy>>,
+) {
+	if keyboard_input.just_pressed(KeyCode::G) {
+		god_mode.0 = !god_mode.0;
+		tainted.0 = true;
+	}
+
+	if keyboard_input.just_pressed(KeyCode::K) {
+		kill_all_enemies(&mut commands, &mut killed_events, &enemy_query);
+		tainted.0 = true;
+	}
+
+	if keyboard_input.just_pressed(KeyCode::N) {
+		let next = wave.number + 1;
+		skip_to_wave(&mut commands, &mut killed_events, &enemy_query, &mut wave, &mut tally, next);
+		tainted.0 = true;
+	}
+}
+
+#[cfg(feature = "devtools")]
+fn update_god_label(
+	god_mode: Res<GodMode>,
+	app_state: Res<State<AppState>>,
+	mut query: Query<&mut Visibility, With<GodLabel>>,
+) {
+	if !god_mode.is_changed() && !app_state.is_changed() {
+		return;
+	}
+
+	let show = god_mode.0 && app_state.0 == AppState::GameRunning;
+	for mut visibility in &mut query {
+		*visibility = if show { Visibility::Visible } else { Visibility::Hidden };
+	}
+}
+
+fn update_event_log(
+	mut commands: Commands,
+	time: Res<Time>,
+	settings: Res<Settings>,
+	theme: Res<Theme>,
+	strings: Res<Strings>,
+	asset_server: Res<AssetServer>,
+	mut messages: EventReader<GameMessage>,
+	log_query: Query<(Entity, Option<&Children>), With<EventLog>>,
+	mut line_query: Query<(&mut EventLogLine, &mut Text)>,
+) {
+	let Ok((log, children)) = log_query.get_single() else {
+		messages.clear();
+		return;
+	};
+
+	// Oldest first, as they were added
+	let mut lines: Vec<Entity> = children
+		.map(|children| children.iter().copied().collect())
+		.unwrap_or_default();
+
+	lines.retain(|&line| {
+		let Ok((mut line_state, mut text)) = line_query.get_mut(line) else {
+			return false;
+		};
+
+		line_state.timer.tick(time.delta());
+		if line_state.timer.finished() {
+			commands.entity(line).despawn_recursive();
+			return false;
+		}
+
+		let alpha = (line_state.timer.remaining_secs() / EVENT_LOG_FADE_SECS).min(1.0);
+		for section in &mut text.sections {
+			section.style.color.set_a(alpha);
+		}
+
+		true
+	});
+
+	// With the log turned off, nothing new is added and what's there fades out
+	if !settings.event_log {
+		messages.clear();
+		return;
+	}
+
+	for message in messages.iter() {
+		if lines.len() >= EVENT_LOG_MAX_LINES {
+			let oldest = lines.remove(0);
+			commands.entity(oldest).despawn_recursive();
+		}
+
+		let line = commands
+			.spawn((
+				TextBundle::from_section(
+					message.0.clone(),
+					TextStyle {
+						font: strings.font(&asset_server),
+						font_size: EVENT_LOG_FONT_SIZE,
+						color: theme.scoreboard,
+					},
+				),
+				EventLogLine {
+					timer: Timer::from_seconds(EVENT_LOG_LINE_SECS, TimerMode::Once),
+				},
+			))
+			.id();
+
+		commands.entity(log).add_child(line);
+		lines.push(line);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use bevy::ecs::schedule::Schedules;
+	use std::collections::HashSet;
+
+	fn challenge(seed: u32) -> ChallengeCode {
+		ChallengeCode {
+			seed,
+			assist: true,
+			mutators: Mutators {
+				tiny_player: true,
+				wrap: true,
+				..default()
+			},
+			difficulty: 1.2,
+		}
+	}
+
+	#[test]
+	fn challenge_codes_round_trip() {
+		let everything = Mutators {
+			bullet_gravity: true,
+			tiny_player: true,
+			ricochet: true,
+			mirror: true,
+			wrap: true,
+		};
+		let codes = [
+			challenge(0xC0FFEE),
+			ChallengeCode { seed: 0, assist: false, mutators: Mutators::default(), difficulty: 0.0 },
+			ChallengeCode { seed: u32::MAX, assist: true, mutators: everything, difficulty: 2.5 },
+		];
+
+		for code in codes {
+			let text = code.encode();
+			assert_eq!(text.len(), CHALLENGE_CODE_LEN);
+			assert_eq!(ChallengeCode::decode(&text), Ok(code));
+			// Typed in lower case, with a stray space
+			assert_eq!(ChallengeCode::decode(&format!(" {} ", text.to_lowercase())), Ok(code));
+		}
+	}
+
+	#[test]
+	fn challenge_code_typos_fail_the_checksum() {
+		let text = challenge(0xC0FFEE).encode();
+
+		// The last character is left out, as one of its bits is only padding
+		for position in 0..CHALLENGE_CODE_LEN - 1 {
+			for &letter in CHALLENGE_ALPHABET {
+				if letter == text.as_bytes()[position] {
+					continue;
+				}
+				let mut typo = text.clone().into_bytes();
+				typo[position] = letter;
+				let typo = String::from_utf8(typo).unwrap();
+				assert_eq!(ChallengeCode::decode(&typo), Err(ChallengeError::Checksum), "{typo}");
+			}
+		}
+	}
+
+	#[test]
+	fn challenge_codes_from_other_versions_are_turned_away() {
+		let mut bytes = challenge(0xC0FFEE).to_bytes();
+		bytes[0] = CHALLENGE_VERSION + 1;
+		bytes[7] = challenge_checksum(&bytes[..7]);
+		assert_eq!(ChallengeCode::decode(&challenge_base32(&bytes)), Err(ChallengeError::Version));
+
+		// Version 1 codes are a character shorter
+		let old = &challenge(0xC0FFEE).encode()[..CHALLENGE_V1_CODE_LEN];
+		assert_eq!(ChallengeCode::decode(old), Err(ChallengeError::Version));
+
+		assert_eq!(ChallengeCode::decode("ABC"), Err(ChallengeError::Length));
+	}
+
+	const DETERMINISM_HASH_TICKS: u32 = 60;
+	const DETERMINISM_RUN_TICKS: u32 = 1200;
+	const DETERMINISM_FIRE_TICKS: u32 = 15;
+	const GOLDEN_HASHES: &str = include_str!("../tests/golden/seeded_run.txt");
+
+	// FNV-1a, so the hashes don't depend on the standard library's hasher
+	fn fnv1a(hash: u64, value: i64) -> u64 {
+		value
+			.to_le_bytes()
+			.iter()
+			.fold(hash, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100_0000_01b3))
+	}
+
+	// Plays a run from a fixed challenge code, firing every so often, and hashes
+	// everything with a SpawnId plus the score every DETERMINISM_HASH_TICKS ticks
+	fn seeded_run_hashes() -> Vec<u64> {
+		let mut app = build_headless_app();
+		app.update();
+		app.world.resource_mut::<ActiveChallenge>().0 = Some(challenge(0xC0FFEE));
+		app.world.resource_mut::<NextState<AppState>>().set(AppState::GameRunning);
+		app.update();
+
+		let mut hashes = Vec::new();
+		for tick in 1..=DETERMINISM_RUN_TICKS {
+			if tick % DETERMINISM_FIRE_TICKS == 0 {
+				app.world.resource_mut::<Events<FireIntent>>().send(FireIntent);
+			}
+			app.update();
+
+			if tick % DETERMINISM_HASH_TICKS == 0 {
+				let mut entities: Vec<(u64, Vec3)> = app
+					.world
+					.query::<(&SpawnId, &Transform)>()
+					.iter(&app.world)
+					.map(|(id, transform)| (id.0, transform.translation))
+					.collect();
+				entities.sort_by_key(|&(id, _)| id);
+
+				let mut hash = fnv1a(0xcbf2_9ce4_8422_2325, app.world.resource::<Scoreboard>().score as i64);
+				for (id, position) in entities {
+					hash = fnv1a(hash, id as i64);
+					// Already on the grid, so these are whole numbers
+					hash = fnv1a(hash, (position.x * POSITION_GRID) as i64);
+					hash = fnv1a(hash, (position.y * POSITION_GRID) as i64);
+				}
+				hashes.push(hash);
+			}
+		}
+		hashes
+	}
+
+	#[test]
+	fn seeded_runs_play_out_the_same() {
+		let hashes = seeded_run_hashes();
+		assert_eq!(hashes, seeded_run_hashes(), "Two runs from the same code played out differently");
+	}
+
+	// Catches a seeded run changing between builds or platforms, which the test
+	// above can't. Ignored until the hashes have been recorded: run it with
+	//     BLESS_GOLDEN=1 cargo test seeded_run -- --ignored
+	// then commit tests/golden/seeded_run.txt and take the #[ignore] off.
+	#[test]
+	#[ignore = "no golden hashes recorded in tests/golden/seeded_run.txt yet"]
+	fn seeded_run_matches_golden_hashes() {
+		let hashes = seeded_run_hashes();
+
+		if std::env::var_os("BLESS_GOLDEN").is_some() {
+			// Keep the comment at the top
+			let mut golden: String = GOLDEN_HASHES
+				.lines()
+				.filter(|line| line.starts_with('#'))
+				.map(|line| format!("{line}\n"))
+				.collect();
+			for hash in &hashes {
+				golden.push_str(&format!("{hash:016x}\n"));
+			}
+			std::fs::write(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden/seeded_run.txt"), golden).unwrap();
+			return;
+		}
+
+		let golden: Vec<u64> = GOLDEN_HASHES
+			.lines()
+			.filter(|line| !line.is_empty() && !line.starts_with('#'))
+			.map(|line| u64::from_str_radix(line, 16).unwrap())
+			.collect();
+		assert!(!golden.is_empty(), "No golden hashes recorded yet, run with BLESS_GOLDEN=1");
+		for (index, (hash, expected)) in hashes.iter().zip(&golden).enumerate() {
+			assert_eq!(hash, expected, "Diverged by tick {}", (index as u32 + 1) * DETERMINISM_HASH_TICKS);
+		}
+		assert_eq!(hashes.len(), golden.len());
+	}
+
+	fn secs(secs: f32) -> std::time::Duration {
+		std::time::Duration::from_secs_f32(secs)
+	}
+
+	#[test]
+	fn overlapping_formation_modifiers_multiply() {
+		let mut modifiers = FormationModifiers::default();
+		assert_eq!(modifiers.multiplier(), 1.0);
+
+		modifiers.push(0.5, 2.0, false);
+		modifiers.push(0.5, 4.0, false);
+		assert_eq!(modifiers.multiplier(), 0.25);
+
+		// The shorter one runs out first and the other carries on alone
+		modifiers.tick(secs(3.0));
+		assert_eq!(modifiers.active.len(), 1);
+		assert_eq!(modifiers.multiplier(), 0.5);
+
+		modifiers.tick(secs(1.0));
+		assert!(modifiers.active.is_empty());
+		assert_eq!(modifiers.multiplier(), 1.0);
+	}
+
+	fn multipliers(modifiers: &FormationModifiers) -> Vec<f32> {
+		modifiers.active.iter().map(|modifier| modifier.multiplier).collect()
+	}
+
+	#[test]
+	fn formation_modifiers_expire_in_timer_order() {
+		let mut modifiers = FormationModifiers::default();
+		// Pushed longest first, so expiry doesn't just follow push order
+		modifiers.push(0.8, 3.0, false);
+		modifiers.push(0.5, 1.0, false);
+		modifiers.push(2.0, 2.0, false);
+
+		modifiers.tick(secs(1.5));
+		assert_eq!(multipliers(&modifiers), [0.8, 2.0]);
+		modifiers.tick(secs(1.0));
+		assert_eq!(multipliers(&modifiers), [0.8]);
+	}
+
+	#[cfg(feature = "devtools")]
+	#[test]
+	fn console_slow_turns_away_bad_numbers() {
+		let mut world = World::new();
+		world.init_resource::<FormationModifiers>();
+
+		let bad = [
+			["nan", "5"],
+			["0.5", "nan"],
+			["inf", "5"],
+			["0.5", "inf"],
+			["-1", "5"],
+			["0.5", "0"],
+			["0.5", "1e9"],
+		];
+		for args in bad {
+			assert!(console_slow(&mut world, &args).is_err(), "slow {}", args.join(" "));
+		}
+		assert!(world.resource::<FormationModifiers>().active.is_empty());
+
+		assert!(console_slow(&mut world, &["0.5", "5"]).is_ok());
+		assert_eq!(world.resource::<FormationModifiers>().active.len(), 1);
+	}
+
+	#[test]
+	fn eased_formation_modifiers_blend_back_to_normal() {
+		let mut modifiers = FormationModifiers::default();
+		modifiers.push(0.5, 2.0, true);
+		modifiers.push(0.5, 10.0, false);
+		assert_eq!(modifiers.multiplier(), 0.25);
+
+		// Halfway through, the eased one is halfway back to 1
+		modifiers.tick(secs(1.0));
+		assert!((modifiers.multiplier() - 0.75 * 0.5).abs() < 1e-6);
+
+		modifiers.tick(secs(1.0));
+		assert_eq!(modifiers.multiplier(), 0.5);
+	}
+
+	fn running_app() -> App {
+		let mut app = build_headless_app();
+		app.update();
+		app.world.resource_mut::<NextState<AppState>>().set(AppState::GameRunning);
+		app.update();
+		app
+	}
+
+	// A bullet that stays put, so only collision_check can get rid of it
+	fn spawn_still_bullet(app: &mut App, faction: Faction, position: Vec3, id: u64) -> Entity {
+		app.world
+			.spawn((
+				Bullet,
+				faction,
+				Transform::from_translation(position),
+				Collider { half_extents: Vec2::splat(4.0) },
+				// Well past anything the run hands out
+				SpawnId(u64::MAX - id),
+			))
+			.id()
+	}
+
+	#[test]
+	fn alien_shots_stop_the_players_and_carry_on() {
+		let mut app = running_app();
+
+		// Halfway down, well clear of the formation and the player
+		let player_shot = spawn_still_bullet(&mut app, Faction::Player, Vec3::ZERO, 0);
+		let alien_shot = spawn_still_bullet(&mut app, Faction::Enemy, Vec3::new(2.0, 2.0, 0.0), 1);
+		// Shots from the same side pass through each other
+		let other_alien_shot = spawn_still_bullet(&mut app, Faction::Enemy, Vec3::new(-2.0, 2.0, 0.0), 2);
+		app.update();
+
+		assert!(app.world.get_entity(player_shot).is_none());
+		assert!(app.world.get_entity(alien_shot).is_some());
+		assert!(app.world.get_entity(other_alien_shot).is_some());
+	}
+
+	#[test]
+	fn alien_shots_end_the_run_unless_in_god_mode() {
+		let mut app = running_app();
+		let player = app
+			.world
+			.query_filtered::<&Transform, With<Player>>()
+			.single(&app.world)
+			.translation;
+
+		// The player's own shots go straight through them
+		let own_shot = spawn_still_bullet(&mut app, Faction::Player, player, 0);
+		app.update();
+		assert!(app.world.get_entity(own_shot).is_some());
+		app.world.despawn(own_shot);
+
+		app.world.resource_mut::<GodMode>().0 = true;
+		let shot = spawn_still_bullet(&mut app, Faction::Enemy, player, 1);
+		app.update();
+		app.update();
+		assert!(app.world.get_entity(shot).is_none());
+		assert_eq!(app.world.resource::<State<AppState>>().0, AppState::GameRunning);
+
+		app.world.resource_mut::<GodMode>().0 = false;
+		let shot = spawn_still_bullet(&mut app, Faction::Enemy, player, 2);
+		app.update();
+		app.update();
+		assert!(app.world.get_entity(shot).is_none());
+		assert_eq!(app.world.resource::<State<AppState>>().0, AppState::GameOver);
+	}
+
+	fn layout_rows(rows: &[&str]) -> Vec<String> {
+		rows.iter().map(|row| row.to_string()).collect()
+	}
+
+	#[test]
+	fn parses_a_formation_layout() {
+		let layout = FormationLayout::parse("wedge", &layout_rows(&["X.X", ".X.", "XXX"])).unwrap();
+		assert_eq!(layout.columns(), 3);
+		assert_eq!(layout.count(), 6);
+		assert_eq!(layout.cells[1], [false, true, false]);
+	}
+
+	#[test]
+	fn ragged_formation_rows_are_rejected() {
+		let err = FormationLayout::parse("ragged", &layout_rows(&["XXX", "XXX", "XX"])).unwrap_err();
+		assert_eq!(err, "ragged, row 3: 2 wide, but row 1 is 3");
+	}
+
+	#[test]
+	fn bad_formation_characters_point_at_their_row() {
+		let err = FormationLayout::parse("typo", &layout_rows(&["XXX", "X?X"])).unwrap_err();
+		assert_eq!(err, "typo, row 2, column 2: '?' isn't X or .");
+	}
+
+	#[test]
+	fn empty_formation_layouts_are_rejected() {
+		assert!(FormationLayout::parse("nothing", &[]).is_err());
+		assert!(FormationLayout::parse("blank", &layout_rows(&[""])).is_err());
+
+		let err = FormationLayout::parse("dots", &layout_rows(&["...", "..."])).unwrap_err();
+		assert_eq!(err, "dots has no enemies in it");
+	}
+
+	// Two rows of enemies 20 either side of their centre, one per column
+	fn formation_columns(columns: &[f32]) -> Vec<(f32, f32)> {
+		columns.iter().flat_map(|&x| [(x, 20.0), (x, 20.0)]).collect()
+	}
+
+	#[test]
+	fn formation_bounds_shrink_as_columns_are_removed() {
+		let full = formation_columns(&[-100.0, 0.0, 100.0]);
+		assert_eq!(formation_bounds(full.into_iter()), Some((-120.0, 120.0)));
+
+		// With the right column gone it can march further right before bouncing
+		let no_right = formation_columns(&[-100.0, 0.0]);
+		assert_eq!(formation_bounds(no_right.into_iter()), Some((-120.0, 20.0)));
+
+		// And then further left too
+		let middle = formation_columns(&[0.0]);
+		assert_eq!(formation_bounds(middle.into_iter()), Some((-20.0, 20.0)));
+	}
+
+	#[test]
+	fn formation_bounds_of_a_single_survivor() {
+		assert_eq!(formation_bounds([(75.0, 12.0)].into_iter()), Some((63.0, 87.0)));
+		assert_eq!(formation_bounds(std::iter::empty()), None);
+	}
+
+	#[test]
+	fn descent_predictions_match_the_march() {
+		// Quarters, so every position is exact and nothing's off by rounding
+		let wall = 300.0;
+		let (mut left, mut right) = (-100.0, 100.0);
+		let mut speed = 2.0;
+		let mut direction = 1.0;
+
+		// The tick each prediction since the last speed-up said it'd drop on
+		let mut predictions: Vec<u32> = Vec::new();
+		let mut drops = 0;
+
+		for tick in 1..=1200 {
+			// As if an enemy were shot every 45 ticks
+			if tick % 45 == 0 {
+				speed += 0.25;
+				predictions.clear();
+			}
+
+			let step = direction * speed;
+			if overshoots_wall(left, right, step, wall) {
+				for predicted in predictions.drain(..) {
+					assert_eq!(predicted, tick, "Speed {speed}, drop {drops}");
+				}
+				direction = -direction;
+				drops += 1;
+			} else {
+				left += step;
+				right += step;
+				predictions.push(tick + ticks_to_descent(left, right, step, wall));
+			}
+		}
+
+		assert!(drops > 10);
+	}
+
+	// A system added twice, say once for each state it should run in, runs twice
+	// a frame in whichever state matches both. That's how update_scoreboard
+	// ended up counting double. Checked before the first update, while the
+	// schedules still hold every system they were given.
+	#[test]
+	fn wrapping_formations_give_no_descent_warning() {
+		let mut app = build_headless_app();
+		app.update();
+		app.world.resource_mut::<Mutators>().wrap = true;
+		app.world.resource_mut::<NextState<AppState>>().set(AppState::GameRunning);
+		app.update();
+
+		// Just past the end of the first lap, which is when it drops
+		let speed = app.world.resource::<GameConfig>().formation_speed;
+		let lap_ticks = (WRAP_LAP_DISTANCE / speed / TIME_STEP).ceil() as u32;
+		let mut warned_ticks = 0;
+		for _ in 0..lap_ticks + 60 {
+			app.update();
+			if app.world.resource::<FormationParams>().time_to_descent > DESCENT_WARNING_SECS {
+				continue;
+			}
+
+			warned_ticks += 1;
+			let mut arrows = app.world.query_filtered::<&Visibility, With<DescentArrow>>();
+			assert!(arrows.iter(&app.world).all(|visibility| *visibility == Visibility::Hidden));
+		}
+		assert!(warned_ticks > 0, "the formation never got close to dropping");
+	}
+
+	#[test]
+	fn no_game_system_is_registered_twice() {
+		let app = build_headless_app();
+		let schedules = app.world.resource::<Schedules>();
+
+		for label in [CoreSchedule::Main, CoreSchedule::FixedUpdate] {
+			let schedule = schedules.get(&label).unwrap();
+			let mut seen = HashSet::new();
+
+			for (_, system, _) in schedule.graph().systems() {
+				let name = system.name();
+				// Only the game's own, and not closures, which all share one name
+				if !name.starts_with("bevy_invaders::") || name.contains("{{closure}}") {
+					continue;
+				}
+				assert!(seen.insert(name.clone()), "{name} is in {label:?} more than once");
+			}
+			assert!(!seen.is_empty(), "Found none of the game's systems in {label:?}");
+		}
+	}
+
+	#[test]
+	fn sounds_prefer_ogg_over_wav() {
+		assert_eq!(
+			find_sound("audio/wave_fanfare"),
+			Some(("audio/wave_fanfare.ogg".to_string(), "ogg"))
+		);
+		// Only shipped as a .wav
+		assert_eq!(find_sound("audio/ui_click"), Some(("audio/ui_click.wav".to_string(), "wav")));
+		assert_eq!(find_sound("audio/not_a_sound"), None);
+	}
+}
@@ -0,0 +1,241 @@
+// Drives the real game through its screens without a window, one fixed tick
+// per update
+use bevy::{
+	app::AppExit,
+	input::{keyboard::KeyboardInput, ButtonState},
+	prelude::*,
+};
+use bevy_invaders::{
+	build_headless_app, AppState, Menu, MenuButtonAction, MutatorButtonAction, Mutators, MutatorsMenu, Player,
+	Scoreboard, SettingsButtonAction, SettingsMenu,
+};
+
+fn current_state(app: &App) -> AppState {
+	app.world.resource::<State<AppState>>().0.clone()
+}
+
+fn go_to(app: &mut App, state: AppState) {
+	app.world.resource_mut::<NextState<AppState>>().set(state);
+	app.update();
+}
+
+// Press and release a key over two updates, like a real tap
+fn tap(app: &mut App, key: KeyCode) {
+	for state in [ButtonState::Pressed, ButtonState::Released] {
+		app.world.resource_mut::<Events<KeyboardInput>>().send(KeyboardInput {
+			scan_code: 0,
+			key_code: Some(key),
+			state,
+		});
+		app.update();
+	}
+}
+
+// Click the button with this action, as ui_focus_system would, then give the
+// screen an update to act on it and another for any state change
+fn click<A: Component + PartialEq>(app: &mut App, action: A) {
+	let button = app
+		.world
+		.query::<(Entity, &A)>()
+		.iter(&app.world)
+		.find(|(_, button)| **button == action)
+		.map(|(entity, _)| entity)
+		.expect("no button for that action on this screen");
+	*app.world.get_mut::<Interaction>(button).unwrap() = Interaction::Clicked;
+
+	app.update();
+	app.update();
+}
+
+fn count<T: Component>(app: &mut App) -> usize {
+	app.world.query_filtered::<(), With<T>>().iter(&app.world).count()
+}
+
+fn quit_requested(app: &App) -> bool {
+	!app.world.resource::<Events<AppExit>>().is_empty()
+}
+
+#[test]
+fn starts_on_the_menu() {
+	let mut app = build_headless_app();
+	app.update();
+
+	assert_eq!(current_state(&app), AppState::Menu);
+}
+
+#[test]
+fn menu_to_game_and_back() {
+	let mut app = build_headless_app();
+	app.update();
+
+	go_to(&mut app, AppState::GameRunning);
+	assert_eq!(current_state(&app), AppState::GameRunning);
+	assert!(app.world.get_resource::<Scoreboard>().is_some());
+
+	// A couple of seconds of play, so the fixed tick systems all get a go
+	for _ in 0..120 {
+		app.update();
+	}
+	assert_eq!(current_state(&app), AppState::GameRunning);
+
+	go_to(&mut app, AppState::Menu);
+	assert_eq!(current_state(&app), AppState::Menu);
+}
+
+#[test]
+fn starting_another_run_resets_the_last_one() {
+	let mut app = build_headless_app();
+	app.update();
+
+	go_to(&mut app, AppState::GameRunning);
+
+	// The first run, then two more after a game over
+	let mut entity_counts = Vec::new();
+	for _ in 0..3 {
+		assert_eq!(app.world.resource::<Scoreboard>().score, 0);
+		entity_counts.push(app.world.entities().len());
+
+		// Score something and let the run carry on for a bit
+		app.world.resource_mut::<Scoreboard>().score = 1234;
+		for _ in 0..60 {
+			app.update();
+		}
+
+		go_to(&mut app, AppState::GameOver);
+		assert_eq!(current_state(&app), AppState::GameOver);
+		go_to(&mut app, AppState::GameRunning);
+	}
+
+	// Nothing left over from the last run, and nothing spawned twice
+	assert!(
+		entity_counts.windows(2).all(|pair| pair[0] == pair[1]),
+		"entity counts changed between runs: {entity_counts:?}",
+	);
+}
+
+#[test]
+fn escape_during_a_run_asks_before_quitting() {
+	let mut app = build_headless_app();
+	app.update();
+	go_to(&mut app, AppState::GameRunning);
+
+	// The quit dialog pauses the game while it's up
+	tap(&mut app, KeyCode::Escape);
+	assert!(app.world.resource::<Time>().is_paused());
+	assert!(!quit_requested(&app));
+	assert_eq!(current_state(&app), AppState::GameRunning);
+
+	// And Esc again cancels it rather than quitting
+	tap(&mut app, KeyCode::Escape);
+	assert!(!app.world.resource::<Time>().is_paused());
+	assert!(!quit_requested(&app));
+	assert_eq!(current_state(&app), AppState::GameRunning);
+}
+
+#[test]
+fn escape_on_the_challenge_screen_goes_back() {
+	let mut app = build_headless_app();
+	app.update();
+	go_to(&mut app, AppState::Challenge);
+
+	tap(&mut app, KeyCode::Escape);
+	app.update();
+	assert_eq!(current_state(&app), AppState::Menu);
+	assert!(!quit_requested(&app));
+}
+
+#[test]
+fn game_over_goes_back_to_the_menu() {
+	let mut app = build_headless_app();
+	app.update();
+
+	for key in [KeyCode::Escape, KeyCode::Return] {
+		go_to(&mut app, AppState::GameRunning);
+		go_to(&mut app, AppState::GameOver);
+
+		tap(&mut app, key);
+		app.update();
+		assert_eq!(current_state(&app), AppState::Menu);
+		assert!(!quit_requested(&app));
+	}
+}
+
+#[test]
+fn escape_on_the_settings_and_mutators_screens_goes_back() {
+	let mut app = build_headless_app();
+	app.update();
+
+	for screen in [AppState::Settings, AppState::Mutators] {
+		go_to(&mut app, screen.clone());
+		assert_eq!(current_state(&app), screen);
+
+		tap(&mut app, KeyCode::Escape);
+		app.update();
+		assert_eq!(current_state(&app), AppState::Menu, "Esc on {screen:?}");
+		assert!(!quit_requested(&app));
+	}
+}
+
+#[test]
+fn play_button_starts_a_run() {
+	let mut app = build_headless_app();
+	app.update();
+	assert!(count::<Menu>(&mut app) > 0);
+
+	click(&mut app, MenuButtonAction::Play);
+	assert_eq!(current_state(&app), AppState::GameRunning);
+	assert_eq!(count::<Menu>(&mut app), 0, "menu_cleanup left menu entities behind");
+	assert_eq!(count::<Player>(&mut app), 1);
+}
+
+#[test]
+fn settings_button_and_back() {
+	let mut app = build_headless_app();
+	app.update();
+
+	click(&mut app, MenuButtonAction::Settings);
+	assert_eq!(current_state(&app), AppState::Settings);
+	assert_eq!(count::<Menu>(&mut app), 0);
+	assert!(count::<SettingsMenu>(&mut app) > 0);
+
+	click(&mut app, SettingsButtonAction::Back);
+	assert_eq!(current_state(&app), AppState::Menu);
+	assert_eq!(count::<SettingsMenu>(&mut app), 0);
+	assert!(count::<Menu>(&mut app) > 0);
+}
+
+#[test]
+fn mutator_buttons_toggle_mutators_for_the_next_run() {
+	let mut app = build_headless_app();
+	app.update();
+
+	click(&mut app, MenuButtonAction::Mutators);
+	assert_eq!(current_state(&app), AppState::Mutators);
+	assert!(count::<MutatorsMenu>(&mut app) > 0);
+	assert_eq!(*app.world.resource::<Mutators>(), Mutators::default());
+
+	click(&mut app, MutatorButtonAction::Wrap);
+	click(&mut app, MutatorButtonAction::Ricochet);
+	click(&mut app, MutatorButtonAction::Ricochet);
+	let picked = Mutators { wrap: true, ..default() };
+	assert_eq!(*app.world.resource::<Mutators>(), picked);
+
+	click(&mut app, MutatorButtonAction::Back);
+	assert_eq!(current_state(&app), AppState::Menu);
+	assert_eq!(count::<MutatorsMenu>(&mut app), 0);
+
+	// They stay picked into the run
+	click(&mut app, MenuButtonAction::Play);
+	assert_eq!(current_state(&app), AppState::GameRunning);
+	assert_eq!(*app.world.resource::<Mutators>(), picked);
+}
+
+#[test]
+fn challenge_button_opens_the_challenge_screen() {
+	let mut app = build_headless_app();
+	app.update();
+
+	click(&mut app, MenuButtonAction::Challenge);
+	assert_eq!(current_state(&app), AppState::Challenge);
+	assert_eq!(count::<Menu>(&mut app), 0);
+}
@@ -0,0 +1,69 @@
+// Ten minutes of play, restarting whenever a run ends, to catch anything that
+// loads a new asset every time it's spawned rather than sharing a handle
+#![cfg(feature = "devtools")]
+
+use bevy::{
+	input::{keyboard::KeyboardInput, ButtonState},
+	prelude::*,
+};
+use bevy_invaders::{build_headless_app, AppState};
+
+// At one fixed tick per update
+const TICKS_PER_MINUTE: u32 = 60 * 60;
+const SOAK_MINUTES: u32 = 10;
+// Long enough for every sound and texture to have been loaded once
+const WARM_UP_MINUTES: u32 = 1;
+const FIRE_TICKS: u32 = 20;
+
+fn asset_counts(app: &App) -> [(&'static str, usize); 4] {
+	let world = &app.world;
+	[
+		("audio", world.resource::<Assets<AudioSource>>().len()),
+		("meshes", world.resource::<Assets<Mesh>>().len()),
+		("materials", world.resource::<Assets<ColorMaterial>>().len()),
+		("images", world.resource::<Assets<Image>>().len()),
+	]
+}
+
+fn press(app: &mut App, key: KeyCode, state: ButtonState) {
+	app.world.resource_mut::<Events<KeyboardInput>>().send(KeyboardInput {
+		scan_code: 0,
+		key_code: Some(key),
+		state,
+	});
+}
+
+#[test]
+fn asset_counts_stay_flat() {
+	let mut app = build_headless_app();
+	app.update();
+
+	let mut warmed_up = None;
+	for tick in 0..SOAK_MINUTES * TICKS_PER_MINUTE {
+		if app.world.resource::<State<AppState>>().0 != AppState::GameRunning {
+			app.world.resource_mut::<NextState<AppState>>().set(AppState::GameRunning);
+		}
+
+		// Keep shooting, so bullets, explosions and their sounds keep coming
+		match tick % FIRE_TICKS {
+			0 => press(&mut app, KeyCode::Space, ButtonState::Pressed),
+			1 => press(&mut app, KeyCode::Space, ButtonState::Released),
+			_ => {},
+		}
+		app.update();
+
+		if tick + 1 == WARM_UP_MINUTES * TICKS_PER_MINUTE {
+			warmed_up = Some(asset_counts(&app));
+		}
+	}
+
+	let before = warmed_up.unwrap();
+	let after = asset_counts(&app);
+	for ((name, before), (_, after)) in before.iter().zip(after) {
+		assert!(
+			after <= *before,
+			"{name} assets went from {before} to {after} over {} minutes",
+			SOAK_MINUTES - WARM_UP_MINUTES,
+		);
+	}
+}